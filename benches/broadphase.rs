@@ -0,0 +1,104 @@
+//! Compares the spatial-hash broadphase against testing every bullet against every target.
+//! Run with `cargo bench --bench broadphase`.
+
+#[allow(dead_code)]
+#[path = "../src/spatial_hash.rs"]
+mod spatial_hash;
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use bevy::math::{Rect, Vec2};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use spatial_hash::SpatialHash;
+
+const CELL_SIZE: f32 = 16.0;
+const ARENA: Vec2 = Vec2::new(256.0, 192.0);
+const BULLET_SIZE: Vec2 = Vec2::new(3.0, 5.0);
+const TARGET_SIZE: Vec2 = Vec2::new(12.0, 8.0);
+const RUNS: u32 = 20;
+
+fn random_boxes(rng: &mut ChaCha8Rng, count: usize, size: Vec2) -> Vec<Rect> {
+    (0..count)
+        .map(|_| {
+            let center = Vec2::new(
+                rng.gen_range(-ARENA.x / 2.0..ARENA.x / 2.0),
+                rng.gen_range(-ARENA.y / 2.0..ARENA.y / 2.0),
+            );
+            Rect::from_center_size(center, size)
+        })
+        .collect()
+}
+
+fn overlaps(a: Rect, b: Rect) -> bool {
+    !a.intersect(b).is_empty()
+}
+
+fn brute_force(bullets: &[Rect], targets: &[Rect]) -> usize {
+    bullets
+        .iter()
+        .map(|bullet| {
+            targets
+                .iter()
+                .filter(|target| overlaps(*bullet, **target))
+                .count()
+        })
+        .sum()
+}
+
+fn broadphase(grid: &mut SpatialHash<usize>, bullets: &[Rect], targets: &[Rect]) -> usize {
+    grid.clear();
+
+    for (i, target) in targets.iter().enumerate() {
+        grid.insert(i, *target);
+    }
+
+    bullets
+        .iter()
+        .map(|bullet| {
+            grid.query(*bullet)
+                .into_iter()
+                .filter(|i| overlaps(*bullet, targets[*i]))
+                .count()
+        })
+        .sum()
+}
+
+fn time(mut run: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut hits = 0;
+    let start = Instant::now();
+
+    for _ in 0..RUNS {
+        hits = black_box(run());
+    }
+
+    (start.elapsed() / RUNS, hits)
+}
+
+fn main() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut grid = SpatialHash::new(CELL_SIZE);
+
+    println!(
+        "{:>8} {:>8} {:>14} {:>14} {:>8}",
+        "bullets", "targets", "brute force", "broadphase", "hits"
+    );
+
+    for (bullet_count, target_count) in [(100, 55), (1_000, 100), (5_000, 300), (10_000, 500)] {
+        let bullets = random_boxes(&mut rng, bullet_count, BULLET_SIZE);
+        let targets = random_boxes(&mut rng, target_count, TARGET_SIZE);
+
+        let (brute_force_time, expected) = time(|| brute_force(&bullets, &targets));
+        let (broadphase_time, hits) = time(|| broadphase(&mut grid, &bullets, &targets));
+
+        assert_eq!(hits, expected, "broadphase missed collisions");
+
+        println!(
+            "{bullet_count:>8} {target_count:>8} {:>14?} {:>14?} {hits:>8}",
+            brute_force_time, broadphase_time
+        );
+    }
+}
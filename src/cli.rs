@@ -0,0 +1,130 @@
+use std::{env, net::SocketAddr, path::PathBuf, process};
+
+use crate::enums::GameMode;
+
+const USAGE: &str =
+    "Usage: space-invaders-boogaloo [--headless] [--frames <count>] [--seed <number>]
+    [--mode <single|co-op|alternating>] [--config <file>] [--waves <file>]
+    [--bindings <file>] [--high-scores <file>] [--record <file> | --replay <file>]
+    [--host <port> | --join <address>] [--net-latency <milliseconds>] [--net-loss <percent>]";
+
+pub struct CliArgs {
+    pub headless: bool,
+    pub frames: Option<u32>,
+    pub seed: Option<u64>,
+    pub mode: Option<GameMode>,
+    pub config: Option<PathBuf>,
+    pub waves: Option<PathBuf>,
+    pub bindings: Option<PathBuf>,
+    pub high_scores: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub host: Option<u16>,
+    pub join: Option<SocketAddr>,
+    pub net_latency: Option<u64>,
+    pub net_loss: Option<f32>,
+}
+
+impl CliArgs {
+    pub fn parse() -> Self {
+        let mut cli_args = CliArgs {
+            headless: false,
+            frames: None,
+            seed: None,
+            mode: None,
+            config: None,
+            waves: None,
+            bindings: None,
+            high_scores: None,
+            record: None,
+            replay: None,
+            host: None,
+            join: None,
+            net_latency: None,
+            net_loss: None,
+        };
+
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => cli_args.headless = true,
+                "--frames" => cli_args.frames = Some(parse_value(&arg, args.next())),
+                "--seed" => cli_args.seed = Some(parse_value(&arg, args.next())),
+                "--mode" => cli_args.mode = Some(parse_value(&arg, args.next())),
+                "--config" => cli_args.config = Some(parse_value(&arg, args.next())),
+                "--waves" => cli_args.waves = Some(parse_value(&arg, args.next())),
+                "--bindings" => cli_args.bindings = Some(parse_value(&arg, args.next())),
+                "--high-scores" => cli_args.high_scores = Some(parse_value(&arg, args.next())),
+                "--record" => cli_args.record = Some(parse_value(&arg, args.next())),
+                "--replay" => cli_args.replay = Some(parse_value(&arg, args.next())),
+                "--host" => cli_args.host = Some(parse_value(&arg, args.next())),
+                "--join" => cli_args.join = Some(parse_value(&arg, args.next())),
+                "--net-latency" => cli_args.net_latency = Some(parse_value(&arg, args.next())),
+                "--net-loss" => cli_args.net_loss = Some(parse_value(&arg, args.next())),
+                "--help" | "-h" => {
+                    println!("{USAGE}");
+                    process::exit(0);
+                }
+                _ => usage_error(&format!("unknown argument '{arg}'")),
+            }
+        }
+
+        if cli_args.record.is_some() && cli_args.replay.is_some() {
+            usage_error("--record and --replay cannot be used together");
+        }
+
+        let netplay = cli_args.host.is_some() || cli_args.join.is_some();
+
+        if cli_args.host.is_some() && cli_args.join.is_some() {
+            usage_error("--host and --join cannot be used together");
+        }
+
+        if netplay && cli_args.replay.is_some() {
+            usage_error("--replay cannot be used with --host or --join");
+        }
+
+        if netplay && cli_args.mode.is_some_and(|mode| mode != GameMode::CoOp) {
+            usage_error("games over the network are always co-op");
+        }
+
+        if cli_args.high_scores.is_some() && (cli_args.replay.is_some() || cli_args.join.is_some())
+        {
+            usage_error(
+                "--high-scores cannot be used with --replay or --join, which bring their own",
+            );
+        }
+
+        if !netplay && (cli_args.net_latency.is_some() || cli_args.net_loss.is_some()) {
+            usage_error("--net-latency and --net-loss need --host or --join");
+        }
+
+        if cli_args
+            .net_loss
+            .is_some_and(|loss| !(0.0..=100.0).contains(&loss))
+        {
+            usage_error("--net-loss must be between 0 and 100");
+        }
+
+        cli_args
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    match value {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            usage_error(&format!("invalid value '{value}' for {flag}"));
+        }),
+        None => usage_error(&format!("missing value for {flag}")),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {message}\n{USAGE}");
+    process::exit(2);
+}
+
+pub fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {message}");
+    process::exit(1);
+}
@@ -0,0 +1,537 @@
+use std::ops::BitOr;
+
+use bevy::prelude::*;
+
+use crate::{components::Shield, spatial_hash::SpatialHash};
+
+/// Side of a broadphase grid cell, about the size of an invader.
+const BROADPHASE_CELL_SIZE: f32 = 16.0;
+
+/// Every sprite texture is this many pixels square, with the visible art centred in it.
+const TEXTURE_SIZE: f32 = 32.0;
+
+/// Visible pixel bounds of each sprite inside its texture, as `[left, top, right, bottom]` with
+/// the right and bottom edges exclusive.
+const SPRITE_BOUNDS: &[(&str, [u32; 4])] = &[
+    ("player.png", [9, 8, 24, 18]),
+    ("player-bullet.png", [15, 13, 16, 16]),
+    ("invader-bullet.png", [15, 13, 18, 18]),
+    ("invader-bolt.png", [15, 13, 18, 18]),
+    ("invader-1-sheet.png", [10, 11, 22, 19]),
+    ("invader-2-sheet.png", [10, 10, 21, 18]),
+    ("invader-3-sheet.png", [12, 11, 20, 19]),
+    ("invader-special.png", [8, 11, 24, 18]),
+];
+
+/// A set of collision layers. Lower bits win when a collider touches several things at once,
+/// so a player shot that meets an invader shot in front of a shield hits the shot first.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CollisionLayers(u32);
+
+impl CollisionLayers {
+    pub const NONE: Self = CollisionLayers(0);
+    pub const INVADER_BULLET: Self = CollisionLayers(1 << 0);
+    pub const SHIELD: Self = CollisionLayers(1 << 1);
+    pub const INVADER: Self = CollisionLayers(1 << 2);
+    pub const MYSTERY_SHIP: Self = CollisionLayers(1 << 3);
+    pub const PLAYER: Self = CollisionLayers(1 << 4);
+    pub const PLAYER_BULLET: Self = CollisionLayers(1 << 5);
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for CollisionLayers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        CollisionLayers(self.0 | other.0)
+    }
+}
+
+/// An axis-aligned box around the entity's translation. `layer` is what the entity is and
+/// `mask` the layers it looks for; only colliders with a non-empty mask report collisions.
+#[derive(Component, Clone, Copy)]
+pub struct Collider {
+    pub size: Vec2,
+    pub offset: Vec2,
+    pub layer: CollisionLayers,
+    pub mask: CollisionLayers,
+}
+
+impl Collider {
+    pub fn new(size: Vec2, layer: CollisionLayers, mask: CollisionLayers) -> Self {
+        Collider {
+            size,
+            offset: Vec2::ZERO,
+            layer,
+            mask,
+        }
+    }
+
+    /// A box around the visible part of `texture`, falling back to the whole texture (with a
+    /// warning) for sprites missing from the bounds table.
+    pub fn from_sprite(texture: &str, layer: CollisionLayers, mask: CollisionLayers) -> Self {
+        let [left, top, right, bottom] =
+            match SPRITE_BOUNDS.iter().find(|(name, _)| *name == texture) {
+                Some((_, bounds)) => bounds.map(|edge| edge as f32),
+                None => {
+                    warn!(
+                        "No collision bounds for {}, colliding with the whole texture",
+                        texture
+                    );
+                    [0.0, 0.0, TEXTURE_SIZE, TEXTURE_SIZE]
+                }
+            };
+
+        Collider {
+            size: Vec2::new(right - left, bottom - top),
+            offset: Vec2::new(
+                (left + right - TEXTURE_SIZE) / 2.0,
+                (TEXTURE_SIZE - top - bottom) / 2.0,
+            ),
+            layer,
+            mask,
+        }
+    }
+
+    pub fn aabb(&self, transform: &Transform) -> Rect {
+        self.aabb_at(transform.translation.truncate())
+    }
+
+    pub fn aabb_at(&self, position: Vec2) -> Rect {
+        Rect::from_center_size(position + self.offset, self.size)
+    }
+}
+
+/// Makes a fast-moving collider test the whole path it travelled this frame rather than only
+/// where it ended up, so it cannot skip over anything thinner than one frame's movement.
+#[derive(Component)]
+pub struct Swept {
+    /// Translation at the start of the frame's movement.
+    pub previous: Vec2,
+}
+
+/// Every collider's box bucketed into a grid, rebuilt each frame before collisions are
+/// detected.
+#[derive(Resource)]
+pub struct Broadphase(pub SpatialHash<Entity>);
+
+impl Default for Broadphase {
+    fn default() -> Self {
+        Broadphase(SpatialHash::new(BROADPHASE_CELL_SIZE))
+    }
+}
+
+/// Colliders used up by a collision this frame. Collision handlers skip any collision involving
+/// one, so a shot destroyed by one handler cannot also score, carve or hit in a later one.
+/// Cleared when the broadphase is rebuilt.
+#[derive(Resource, Default)]
+pub struct SpentColliders(Vec<Entity>);
+
+impl SpentColliders {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+
+    /// Whether either side of `collision` has already been used up.
+    pub fn involves(&self, collision: &CollisionEvent) -> bool {
+        self.contains(collision.entity) || self.contains(collision.other)
+    }
+
+    pub fn spend(&mut self, entity: Entity) {
+        self.0.push(entity);
+    }
+}
+
+/// `entity`'s collider touched `other`, which is on one of the layers in `entity`'s mask.
+/// Each entity reports at most one collision per frame: the first thing it touched along its
+/// path, with ties going to the lowest layer.
+#[derive(Event, Clone, Copy)]
+pub struct CollisionEvent {
+    pub entity: Entity,
+    pub other: Entity,
+    pub layer: CollisionLayers,
+    /// `entity`'s translation at the moment of contact.
+    pub point: Vec2,
+}
+
+pub fn rebuild_broadphase(
+    mut broadphase: ResMut<Broadphase>,
+    mut spent: ResMut<SpentColliders>,
+    colliders: Query<(Entity, &Transform, &Collider, Option<&Swept>)>,
+) {
+    broadphase.0.clear();
+    spent.0.clear();
+
+    for (entity, transform, collider, swept) in colliders.iter() {
+        let aabb = collider.aabb(transform);
+        let aabb = match swept {
+            Some(swept) => aabb.union(collider.aabb_at(swept.previous)),
+            None => aabb,
+        };
+
+        broadphase.0.insert(entity, aabb);
+    }
+}
+
+/// Shields are tested pixel by pixel rather than by their bounding box, so shots fly through
+/// the holes blown in them.
+#[allow(clippy::type_complexity)]
+pub fn detect_collisions(
+    broadphase: Res<Broadphase>,
+    colliders: Query<(
+        Entity,
+        &Transform,
+        &Collider,
+        Option<&Shield>,
+        Option<&Swept>,
+    )>,
+    mut events: EventWriter<CollisionEvent>,
+) {
+    for (entity, transform, collider, _, swept) in colliders
+        .iter()
+        .filter(|(_, _, collider, _, _)| collider.mask != CollisionLayers::NONE)
+    {
+        let end = transform.translation.truncate();
+        let start = swept.map_or(end, |swept| swept.previous);
+        let delta = end - start;
+        let start_aabb = collider.aabb_at(start);
+
+        let first_contact = broadphase
+            .0
+            .query(start_aabb.union(collider.aabb_at(end)))
+            .into_iter()
+            .filter_map(|other| colliders.get(other).ok())
+            .filter(|(other, _, other_collider, _, _)| {
+                *other != entity && collider.mask.intersects(other_collider.layer)
+            })
+            .filter_map(
+                |(other, other_transform, other_collider, shield, other_swept)| {
+                    // Against another moving collider, sweep with the motion relative to it so
+                    // shots passing each other within one frame still meet.
+                    let time = match other_swept {
+                        Some(other_swept) => {
+                            let other_end = other_transform.translation.truncate();
+                            let other_delta = other_end - other_swept.previous;
+
+                            sweep(
+                                start_aabb,
+                                delta - other_delta,
+                                other_collider.aabb_at(other_swept.previous),
+                            )?
+                        }
+                        None => sweep(start_aabb, delta, other_collider.aabb(other_transform))?,
+                    };
+                    let time = match shield {
+                        Some(shield) => sweep_shield(shield, start_aabb, delta, time)?,
+                        None => time,
+                    };
+
+                    Some((time, other_collider.layer, other))
+                },
+            )
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1 .0.cmp(&b.1 .0)));
+
+        if let Some((time, layer, other)) = first_contact {
+            events.send(CollisionEvent {
+                entity,
+                other,
+                layer,
+                point: start + delta * time,
+            });
+        }
+    }
+}
+
+/// The fraction of `delta` at which a box moving from `start` first overlaps `target`, or
+/// `None` if it never does. Boxes that only touch edges do not overlap.
+fn sweep(start: Rect, delta: Vec2, target: Rect) -> Option<f32> {
+    let half_size = start.half_size();
+    let expanded = Rect::from_corners(target.min - half_size, target.max + half_size);
+    let origin = start.center();
+
+    let mut enter: f32 = 0.0;
+    let mut exit: f32 = 1.0;
+
+    for axis in 0..2 {
+        let (origin, delta, min, max) = (
+            origin[axis],
+            delta[axis],
+            expanded.min[axis],
+            expanded.max[axis],
+        );
+
+        if delta == 0.0 {
+            if origin <= min || origin >= max {
+                return None;
+            }
+        } else {
+            let (near, far) = ((min - origin) / delta, (max - origin) / delta);
+            enter = enter.max(near.min(far));
+            exit = exit.min(near.max(far));
+        }
+    }
+
+    (enter < exit).then_some(enter)
+}
+
+/// Walks a box moving from `start` along `delta` a pixel at a time, beginning at `from`, and
+/// returns the fraction of `delta` at which it first covers a solid shield pixel.
+fn sweep_shield(shield: &Shield, start: Rect, delta: Vec2, from: f32) -> Option<f32> {
+    let steps = (delta.length() * (1.0 - from)).ceil().max(1.0);
+
+    (0..=steps as u32)
+        .map(|step| from + (1.0 - from) * step as f32 / steps)
+        .find(|time| {
+            let offset = delta * *time;
+            shield.overlaps(Rect::from_corners(start.min + offset, start.max + offset))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn sweep_finds_when_a_moving_box_enters_the_target() {
+        let start = Rect::new(0.0, 0.0, 2.0, 2.0);
+        let target = Rect::new(10.0, 0.0, 12.0, 2.0);
+
+        assert_eq!(sweep(start, Vec2::new(16.0, 0.0), target), Some(0.5));
+        assert_eq!(sweep(start, Vec2::new(4.0, 0.0), target), None);
+        assert_eq!(sweep(start, Vec2::new(0.0, 16.0), target), None);
+    }
+
+    #[test]
+    fn sweep_catches_targets_thinner_than_one_step() {
+        let start = Rect::new(0.0, -1.0, 1.0, 0.0);
+        let target = Rect::new(0.0, 10.0, 1.0, 10.5);
+
+        assert!(sweep(start, Vec2::new(0.0, 40.0), target).is_some());
+    }
+
+    #[test]
+    fn sweep_ignores_boxes_only_touching_edges() {
+        let start = Rect::new(0.0, 0.0, 2.0, 2.0);
+
+        assert_eq!(
+            sweep(start, Vec2::ZERO, Rect::new(2.0, 0.0, 4.0, 2.0)),
+            None
+        );
+        assert_eq!(
+            sweep(start, Vec2::ZERO, Rect::new(1.0, 1.0, 3.0, 3.0)),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn sweep_shield_stops_at_the_first_solid_pixel() {
+        let shield = Shield::from_shape(&["#", ".", "#", "#"], Vec2::new(0.0, 4.0));
+        let start = Rect::new(0.0, 5.0, 1.0, 6.0);
+        let delta = Vec2::new(0.0, -10.0);
+
+        assert_eq!(sweep_shield(&shield, start, delta, 0.0), Some(0.2));
+
+        let holed = Shield::from_shape(&[".", ".", ".", "."], Vec2::new(0.0, 4.0));
+        assert_eq!(sweep_shield(&holed, start, delta, 0.0), None);
+    }
+
+    fn spawn(
+        world: &mut World,
+        position: Vec2,
+        layer: CollisionLayers,
+        mask: CollisionLayers,
+    ) -> Entity {
+        world
+            .spawn((
+                Transform::from_translation(position.extend(0.0)),
+                Collider::new(Vec2::new(2.0, 2.0), layer, mask),
+            ))
+            .id()
+    }
+
+    fn shot(world: &mut World, previous: Vec2, position: Vec2, layer: CollisionLayers) -> Entity {
+        let mask = if layer == CollisionLayers::PLAYER_BULLET {
+            CollisionLayers::INVADER_BULLET
+        } else {
+            CollisionLayers::NONE
+        };
+
+        world
+            .spawn((
+                Transform::from_translation(position.extend(0.0)),
+                Collider::new(Vec2::new(1.0, 4.0), layer, mask),
+                Swept { previous },
+            ))
+            .id()
+    }
+
+    fn detect(world: &mut World) -> Vec<CollisionEvent> {
+        world.run_system_once(rebuild_broadphase);
+        world.run_system_once(detect_collisions);
+
+        let events = world.resource::<Events<CollisionEvent>>();
+        events.get_reader().read(events).copied().collect()
+    }
+
+    fn collision_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Broadphase>();
+        world.init_resource::<SpentColliders>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world
+    }
+
+    #[test]
+    fn from_sprite_fits_the_visible_pixels() {
+        let collider =
+            Collider::from_sprite("player.png", CollisionLayers::PLAYER, CollisionLayers::NONE);
+
+        assert_eq!(collider.size, Vec2::new(15.0, 10.0));
+        assert_eq!(collider.offset, Vec2::new(0.5, 3.0));
+    }
+
+    #[test]
+    fn collisions_report_the_lowest_layer_touched() {
+        let mut world = collision_world();
+        let shot = spawn(
+            &mut world,
+            Vec2::ZERO,
+            CollisionLayers::PLAYER_BULLET,
+            CollisionLayers::INVADER_BULLET | CollisionLayers::INVADER,
+        );
+        spawn(
+            &mut world,
+            Vec2::new(1.0, 0.0),
+            CollisionLayers::INVADER,
+            CollisionLayers::NONE,
+        );
+        let invader_shot = spawn(
+            &mut world,
+            Vec2::new(0.0, 1.0),
+            CollisionLayers::INVADER_BULLET,
+            CollisionLayers::NONE,
+        );
+
+        let collisions = detect(&mut world);
+
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].entity, shot);
+        assert_eq!(collisions[0].other, invader_shot);
+        assert_eq!(collisions[0].layer, CollisionLayers::INVADER_BULLET);
+    }
+
+    #[test]
+    fn swept_colliders_hit_what_they_passed_through() {
+        let mut world = collision_world();
+        let shot = spawn(
+            &mut world,
+            Vec2::new(0.0, 40.0),
+            CollisionLayers::PLAYER_BULLET,
+            CollisionLayers::INVADER,
+        );
+        world.entity_mut(shot).insert(Swept {
+            previous: Vec2::ZERO,
+        });
+        let invader = spawn(
+            &mut world,
+            Vec2::new(0.0, 20.0),
+            CollisionLayers::INVADER,
+            CollisionLayers::NONE,
+        );
+
+        let collisions = detect(&mut world);
+
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].other, invader);
+        assert_eq!(collisions[0].point, Vec2::new(0.0, 18.0));
+    }
+
+    #[test]
+    fn only_layers_in_the_mask_collide() {
+        let mut world = collision_world();
+        spawn(
+            &mut world,
+            Vec2::ZERO,
+            CollisionLayers::PLAYER_BULLET,
+            CollisionLayers::INVADER,
+        );
+        spawn(
+            &mut world,
+            Vec2::new(1.0, 0.0),
+            CollisionLayers::SHIELD,
+            CollisionLayers::NONE,
+        );
+
+        assert!(detect(&mut world).is_empty());
+    }
+
+    #[test]
+    fn shots_passing_each_other_in_one_frame_collide() {
+        let mut world = collision_world();
+        // Each ends the frame beyond where the other started.
+        let player_shot = shot(
+            &mut world,
+            Vec2::new(0.0, 10.0),
+            Vec2::new(0.0, 30.0),
+            CollisionLayers::PLAYER_BULLET,
+        );
+        let invader_shot = shot(
+            &mut world,
+            Vec2::new(0.0, 25.0),
+            Vec2::new(0.0, 5.0),
+            CollisionLayers::INVADER_BULLET,
+        );
+
+        let collisions = detect(&mut world);
+
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].entity, player_shot);
+        assert_eq!(collisions[0].other, invader_shot);
+        assert_eq!(collisions[0].layer, CollisionLayers::INVADER_BULLET);
+    }
+
+    #[test]
+    fn shots_in_different_columns_do_not_collide() {
+        let mut world = collision_world();
+        shot(
+            &mut world,
+            Vec2::new(0.0, 10.0),
+            Vec2::new(0.0, 30.0),
+            CollisionLayers::PLAYER_BULLET,
+        );
+        shot(
+            &mut world,
+            Vec2::new(3.0, 25.0),
+            Vec2::new(3.0, 5.0),
+            CollisionLayers::INVADER_BULLET,
+        );
+
+        assert!(detect(&mut world).is_empty());
+    }
+
+    #[test]
+    fn spent_colliders_are_forgotten_on_rebuild() {
+        let mut world = collision_world();
+        let entity = world.spawn_empty().id();
+        let other = world.spawn_empty().id();
+
+        world.resource_mut::<SpentColliders>().spend(other);
+
+        let spent = world.resource::<SpentColliders>();
+        assert!(spent.involves(&CollisionEvent {
+            entity,
+            other,
+            layer: CollisionLayers::INVADER,
+            point: Vec2::ZERO,
+        }));
+        assert!(!spent.contains(entity));
+
+        world.run_system_once(rebuild_broadphase);
+        assert!(!world.resource::<SpentColliders>().contains(other));
+    }
+}
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::enums::InvaderBulletType;
+use crate::enums::{InvaderBulletType, InvaderType};
 
 #[derive(Component)]
 pub struct Player {
@@ -9,8 +10,17 @@ pub struct Player {
     pub score: u32,
     pub id: PlayerID,
     pub is_hit: bool,
+    /// 1 for the first player, 2 for the second.
+    pub number: u32,
 }
 
+#[derive(Component)]
+pub struct PlayerShootTimer(pub Timer);
+
+/// Marks the player sitting out while the other takes their turn in an alternating game.
+#[derive(Component)]
+pub struct AwaitingTurn {}
+
 #[derive(Component)]
 pub struct PlayerID {
     pub id: u32,
@@ -22,23 +32,176 @@ impl Clone for PlayerID {
     }
 }
 
+/// A bunker, eroded pixel by pixel. `pixels` is row-major with the top row first and
+/// `top_left` is the world position of the mask's top-left corner.
+#[derive(Component, Clone)]
+pub struct Shield {
+    pub pixels: Vec<bool>,
+    pub width: usize,
+    pub height: usize,
+    pub top_left: Vec2,
+}
+
+impl Shield {
+    /// Builds a shield from rows of `#` (solid) and `.` (empty) characters.
+    pub fn from_shape(shape: &[&str], top_left: Vec2) -> Self {
+        Shield {
+            pixels: shape
+                .iter()
+                .flat_map(|row| row.chars().map(|pixel| pixel == '#'))
+                .collect(),
+            width: shape[0].len(),
+            height: shape.len(),
+            top_left,
+        }
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.top_left + Vec2::new(self.width as f32 / 2.0, -(self.height as f32) / 2.0)
+    }
+
+    fn pixel_at(&self, point: Vec2) -> Option<(usize, usize)> {
+        let x = (point.x - self.top_left.x).floor();
+        let y = (self.top_left.y - point.y).floor();
+
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+
+        Some((x as usize, y as usize))
+    }
+
+    /// Indices into `pixels` of every pixel whose centre lies inside `area`.
+    fn pixels_in(&self, area: Rect) -> Vec<usize> {
+        let mut indices = Vec::new();
+        let mut y = area.min.y.floor() + 0.5;
+
+        while y < area.max.y {
+            let mut x = area.min.x.floor() + 0.5;
+
+            while x < area.max.x {
+                if let Some((px, py)) = self.pixel_at(Vec2::new(x, y)) {
+                    indices.push(py * self.width + px);
+                }
+                x += 1.0;
+            }
+            y += 1.0;
+        }
+
+        indices
+    }
+
+    /// Whether any solid pixel lies inside `area`.
+    pub fn overlaps(&self, area: Rect) -> bool {
+        self.pixels_in(area).into_iter().any(|i| self.pixels[i])
+    }
+
+    /// Clears every pixel inside `area`, returning whether any were solid.
+    pub fn clear(&mut self, area: Rect) -> bool {
+        let mut cleared = false;
+
+        for i in self.pixels_in(area) {
+            cleared |= self.pixels[i];
+            self.pixels[i] = false;
+        }
+
+        cleared
+    }
+
+    /// Clears every pixel covered by `pattern` (rows of `#` and `.`) centred on `point`.
+    pub fn carve(&mut self, point: Vec2, pattern: &[&str]) {
+        let pattern_height = pattern.len() as f32;
+
+        for (row, cells) in pattern.iter().enumerate() {
+            let pattern_width = cells.len() as f32;
+
+            for (col, cell) in cells.chars().enumerate() {
+                if cell != '#' {
+                    continue;
+                }
+
+                let offset = Vec2::new(
+                    col as f32 - pattern_width / 2.0 + 0.5,
+                    pattern_height / 2.0 - row as f32 - 0.5,
+                );
+
+                if let Some((px, py)) = self.pixel_at(point + offset) {
+                    self.pixels[py * self.width + px] = false;
+                }
+            }
+        }
+    }
+
+    /// RGBA8 pixel data for the shield's texture.
+    pub fn to_rgba(&self, color: [u8; 4]) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|solid| if *solid { color } else { [0, 0, 0, 0] })
+            .collect()
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+pub struct Invader {
+    pub invader_type: InvaderType,
+    /// The formation column the invader started in.
+    pub column: usize,
+}
+
+/// Steps a sprite through frames `first..=last` of its [`TextureAtlas`]. Animations with a
+/// timer advance on their own; the rest only move when [`SpriteAnimation::advance`] is called,
+/// which is how invaders keep their walk in step with the march.
 #[derive(Component)]
-pub struct ShieldPart {
-    pub health: u32,
-    pub textures: Vec<Handle<Image>>,
-    pub x: f32,
-    pub y: f32,
+pub struct SpriteAnimation {
+    pub first: usize,
+    pub last: usize,
+    pub timer: Option<Timer>,
+    /// Despawn the entity after the last frame instead of looping back to the first.
+    pub despawn_when_done: bool,
 }
 
-impl ShieldPart {
-    pub fn current_texture(&self) -> Handle<Image> {
-        self.textures[self.health as usize - 1].clone()
+impl SpriteAnimation {
+    pub fn stepped(first: usize, last: usize) -> Self {
+        SpriteAnimation {
+            first,
+            last,
+            timer: None,
+            despawn_when_done: false,
+        }
+    }
+
+    pub fn timed(first: usize, last: usize, frame_seconds: f32, despawn_when_done: bool) -> Self {
+        SpriteAnimation {
+            first,
+            last,
+            timer: Some(Timer::from_seconds(frame_seconds, TimerMode::Repeating)),
+            despawn_when_done,
+        }
+    }
+
+    /// Moves `atlas` on to the next frame. Returns false once an animation that despawns when
+    /// done has shown its last frame.
+    pub fn advance(&self, atlas: &mut TextureAtlas) -> bool {
+        if atlas.index < self.last {
+            atlas.index += 1;
+        } else if self.despawn_when_done {
+            return false;
+        } else {
+            atlas.index = self.first;
+        }
+
+        true
     }
 }
 
+/// The bonus ship that crosses above the formation. It is not an [`Invader`], so the wave can
+/// be cleared while it is still on screen.
 #[derive(Component)]
-pub struct Invader {}
+pub struct MysteryShip {
+    pub velocity: f32,
+}
 
+/// Marks the lowest surviving invader of each column, the only ones allowed to fire.
 #[derive(Component)]
 pub struct Shooter {}
 
@@ -57,6 +220,7 @@ pub struct InvaderBullet {
     pub bullet_type: InvaderBulletType,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct InvaderBulletProbability {
     pub bullet_type: InvaderBulletType,
     pub probability: f32,
@@ -65,13 +229,24 @@ pub struct InvaderBulletProbability {
 #[derive(Component)]
 pub struct PlayerScore {
     pub player_id: PlayerID,
+    pub label: String,
 }
 
 #[derive(Component)]
 pub struct PlayerLife {
     pub player_id: PlayerID,
+    /// Position in the row of icons, counting from zero.
+    pub index: u32,
 }
 
+/// The HI-SCORE readout at the top of the screen.
+#[derive(Component)]
+pub struct HighScoreText {}
+
+/// The "Lives" label in front of a player's life icons.
+#[derive(Component)]
+pub struct PlayerLivesText {}
+
 #[derive(Component)]
 pub struct GameOverText {}
 
@@ -80,3 +255,63 @@ pub struct GameRestartButton {}
 
 #[derive(Component)]
 pub struct GameRestartText {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_shield() -> Shield {
+        Shield::from_shape(&["####", "####", "####", "####"], Vec2::new(0.0, 4.0))
+    }
+
+    #[test]
+    fn from_shape_reads_rows_top_first() {
+        let shield = Shield::from_shape(&["#..", "###"], Vec2::ZERO);
+
+        assert_eq!((shield.width, shield.height), (3, 2));
+        assert_eq!(shield.pixels, [true, false, false, true, true, true]);
+        assert_eq!(shield.center(), Vec2::new(1.5, -1.0));
+    }
+
+    #[test]
+    fn overlaps_only_solid_pixels() {
+        let shield = Shield::from_shape(&["#.", ".."], Vec2::new(0.0, 2.0));
+
+        assert!(shield.overlaps(Rect::new(0.0, 1.0, 1.0, 2.0)));
+        assert!(!shield.overlaps(Rect::new(1.0, 1.0, 2.0, 2.0)));
+        assert!(!shield.overlaps(Rect::new(0.0, 0.0, 2.0, 1.0)));
+        assert!(!shield.overlaps(Rect::new(10.0, 10.0, 12.0, 12.0)));
+    }
+
+    #[test]
+    fn clear_reports_whether_anything_was_solid() {
+        let mut shield = solid_shield();
+        let area = Rect::new(0.0, 3.0, 2.0, 4.0);
+
+        assert!(shield.clear(area));
+        assert!(!shield.clear(area));
+        assert!(!shield.overlaps(area));
+        assert_eq!(shield.pixels.iter().filter(|solid| **solid).count(), 14);
+    }
+
+    #[test]
+    fn carve_clears_the_pattern_around_the_point() {
+        let mut shield = solid_shield();
+        shield.carve(Vec2::new(2.0, 2.0), &["#.", ".#"]);
+
+        let holes: Vec<usize> = (0..shield.pixels.len())
+            .filter(|i| !shield.pixels[*i])
+            .collect();
+
+        assert_eq!(holes, [5, 10]);
+    }
+
+    #[test]
+    fn carve_ignores_pixels_off_the_shield() {
+        let mut shield = solid_shield();
+        shield.carve(Vec2::new(0.0, 4.0), &["##", "##"]);
+
+        assert_eq!(shield.pixels.iter().filter(|solid| !**solid).count(), 1);
+        assert!(!shield.pixels[0]);
+    }
+}
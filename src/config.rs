@@ -0,0 +1,572 @@
+use std::{fmt, fs, io, path::Path};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    components::InvaderBulletProbability,
+    enums::{BulletClashOutcome, InvaderBulletType, InvaderFireMode, InvaderType},
+};
+
+/// Loaded when no `--config` flag is given. A missing file here simply means "use the defaults".
+pub const DEFAULT_CONFIG_PATH: &str = "config.ron";
+
+/// Tunable gameplay values. Any field left out of the config file keeps its default.
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub seed: Option<u64>,
+    pub window: WindowConfig,
+    pub player: PlayerConfig,
+    pub invaders: InvadersConfig,
+    pub mystery_ship: MysteryShipConfig,
+    pub scoring: ScoringConfig,
+    pub bullet_clashes: BulletClashConfig,
+    pub timers: TimersConfig,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerConfig {
+    pub speed: f32,
+    pub lives: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InvadersConfig {
+    pub speed: f32,
+    pub cols: usize,
+    /// Rows of crabs, and as many again of octopuses, in the classic formation.
+    pub decoy_rows: usize,
+    /// Rows of squids at the top of the classic formation.
+    pub squid_rows: usize,
+    /// Chance each invader at the front of its column fires when the shoot timer goes off,
+    /// before its type's fire weight is applied.
+    pub shoot_probability: f32,
+    pub fire_weights: FireWeights,
+    /// Shape of the march speed-up as invaders die: 1.0 shortens the step interval in
+    /// proportion to the invaders killed, larger values hold back the speed-up until late in
+    /// the wave.
+    pub march_curve: f32,
+    pub fire_mode: InvaderFireMode,
+    pub bullet_probabilities: Vec<InvaderBulletProbability>,
+    /// Top sideways speed of homing shots.
+    pub homing_drift: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MysteryShipConfig {
+    pub speed: f32,
+    /// The ship only appears while at least this many invaders are left.
+    pub min_invaders: usize,
+}
+
+/// Points awarded for each kill.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringConfig {
+    pub octopus: u32,
+    pub crab: u32,
+    pub squid: u32,
+    /// One of these is picked at random each time the mystery ship is shot down.
+    pub mystery_ship: Vec<u32>,
+}
+
+/// Multipliers on `shoot_probability` for each invader type.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FireWeights {
+    pub octopus: f32,
+    pub crab: f32,
+    pub squid: f32,
+}
+
+/// What a player shot does to each kind of invader shot it runs into.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BulletClashConfig {
+    pub bullet: BulletClashRule,
+    pub bolt: BulletClashRule,
+    pub homing: BulletClashRule,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BulletClashRule {
+    pub outcome: BulletClashOutcome,
+    /// Awarded when the invader shot is destroyed.
+    #[serde(default)]
+    pub points: u32,
+}
+
+/// Durations in seconds.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimersConfig {
+    pub player_shoot: f32,
+    pub invader_move: f32,
+    /// Step interval once a single invader is left.
+    pub invader_move_fastest: f32,
+    pub invader_shoot: f32,
+    pub player_hit: f32,
+    pub player_hit_animation: f32,
+    pub respawning_invaders: f32,
+    pub mystery_ship: f32,
+    /// How long a shot invader's explosion stays on screen.
+    pub invader_explosion: f32,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            width: 640.0,
+            height: 480.0,
+        }
+    }
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        PlayerConfig {
+            speed: 100.0,
+            lives: 3,
+        }
+    }
+}
+
+impl Default for InvadersConfig {
+    fn default() -> Self {
+        InvadersConfig {
+            speed: 250.0,
+            cols: 11,
+            decoy_rows: 2,
+            squid_rows: 1,
+            shoot_probability: 0.10,
+            fire_weights: FireWeights::default(),
+            fire_mode: InvaderFireMode::Random,
+            march_curve: 1.0,
+            bullet_probabilities: vec![
+                InvaderBulletProbability {
+                    bullet_type: InvaderBulletType::Bullet,
+                    probability: 0.75,
+                },
+                InvaderBulletProbability {
+                    bullet_type: InvaderBulletType::Bolt,
+                    probability: 0.25,
+                },
+            ],
+            homing_drift: 30.0,
+        }
+    }
+}
+
+impl Default for MysteryShipConfig {
+    fn default() -> Self {
+        MysteryShipConfig {
+            speed: 40.0,
+            min_invaders: 8,
+        }
+    }
+}
+
+impl Default for FireWeights {
+    fn default() -> Self {
+        FireWeights {
+            octopus: 1.0,
+            crab: 1.5,
+            squid: 2.0,
+        }
+    }
+}
+
+impl FireWeights {
+    pub fn weight(&self, invader_type: InvaderType) -> f32 {
+        match invader_type {
+            InvaderType::Octopus => self.octopus,
+            InvaderType::Crab => self.crab,
+            InvaderType::Squid => self.squid,
+        }
+    }
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig {
+            octopus: 10,
+            crab: 20,
+            squid: 30,
+            mystery_ship: vec![50, 100, 150, 300],
+        }
+    }
+}
+
+impl ScoringConfig {
+    pub fn invader_points(&self, invader_type: InvaderType) -> u32 {
+        match invader_type {
+            InvaderType::Octopus => self.octopus,
+            InvaderType::Crab => self.crab,
+            InvaderType::Squid => self.squid,
+        }
+    }
+}
+
+impl Default for BulletClashConfig {
+    fn default() -> Self {
+        BulletClashConfig {
+            bullet: BulletClashRule {
+                outcome: BulletClashOutcome::BothDestroyed,
+                points: 0,
+            },
+            bolt: BulletClashRule {
+                outcome: BulletClashOutcome::PlayerShotAbsorbed,
+                points: 0,
+            },
+            homing: BulletClashRule {
+                outcome: BulletClashOutcome::BothDestroyed,
+                points: 0,
+            },
+        }
+    }
+}
+
+impl BulletClashConfig {
+    pub fn rule(&self, bullet_type: InvaderBulletType) -> &BulletClashRule {
+        match bullet_type {
+            InvaderBulletType::Bullet => &self.bullet,
+            InvaderBulletType::Bolt => &self.bolt,
+            InvaderBulletType::Homing => &self.homing,
+        }
+    }
+}
+
+impl Default for TimersConfig {
+    fn default() -> Self {
+        TimersConfig {
+            player_shoot: 0.5,
+            invader_move: 0.85,
+            invader_move_fastest: 0.05,
+            invader_shoot: 1.0,
+            player_hit: 1.5,
+            player_hit_animation: 0.1,
+            respawning_invaders: 1.0,
+            mystery_ship: 25.0,
+            invader_explosion: 0.15,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "{error}"),
+            ConfigError::Parse(error) => write!(f, "invalid config file: {error}"),
+            ConfigError::Invalid(message) => write!(f, "invalid config: {message}"),
+        }
+    }
+}
+
+impl GameConfig {
+    /// Reads the config at `path` through [`load_ron`], falling back to the defaults.
+    pub fn load(path: &Path, required: bool) -> Result<Self, ConfigError> {
+        load_ron(
+            path,
+            required,
+            ron::Options::default(),
+            GameConfig::default,
+            GameConfig::validate,
+        )
+    }
+
+    /// Seconds between invader steps with `remaining` of a wave's `total` invaders left.
+    pub fn march_interval(&self, remaining: usize, total: usize) -> f32 {
+        let slowest = self.timers.invader_move;
+        let fastest = self.timers.invader_move_fastest;
+
+        if total <= 1 {
+            return slowest;
+        }
+
+        let killed = 1.0 - (remaining.saturating_sub(1) as f32 / (total - 1) as f32).min(1.0);
+
+        slowest - (slowest - fastest) * killed.powf(self.invaders.march_curve)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        positive("window.width", self.window.width)?;
+        positive("window.height", self.window.height)?;
+        positive("player.speed", self.player.speed)?;
+        positive("invaders.speed", self.invaders.speed)?;
+        positive("timers.player_shoot", self.timers.player_shoot)?;
+        positive("timers.invader_move", self.timers.invader_move)?;
+        positive(
+            "timers.invader_move_fastest",
+            self.timers.invader_move_fastest,
+        )?;
+        positive("timers.invader_shoot", self.timers.invader_shoot)?;
+        positive("timers.player_hit", self.timers.player_hit)?;
+        positive(
+            "timers.player_hit_animation",
+            self.timers.player_hit_animation,
+        )?;
+        positive(
+            "timers.respawning_invaders",
+            self.timers.respawning_invaders,
+        )?;
+        positive("timers.mystery_ship", self.timers.mystery_ship)?;
+        positive("timers.invader_explosion", self.timers.invader_explosion)?;
+        positive("mystery_ship.speed", self.mystery_ship.speed)?;
+        positive("invaders.march_curve", self.invaders.march_curve)?;
+        positive("invaders.homing_drift", self.invaders.homing_drift)?;
+
+        if self.timers.invader_move_fastest > self.timers.invader_move {
+            return Err(invalid(
+                "timers.invader_move_fastest must not be longer than timers.invader_move",
+            ));
+        }
+
+        if self.player.lives == 0 {
+            return Err(invalid("player.lives must be at least 1"));
+        }
+
+        if self.invaders.cols == 0 {
+            return Err(invalid("invaders.cols must be at least 1"));
+        }
+
+        if self.invaders.decoy_rows + self.invaders.squid_rows == 0 {
+            return Err(invalid(
+                "invaders.decoy_rows and invaders.squid_rows cannot both be 0",
+            ));
+        }
+
+        if self.scoring.mystery_ship.is_empty() {
+            return Err(invalid("scoring.mystery_ship must not be empty"));
+        }
+
+        probability(
+            "invaders.shoot_probability",
+            self.invaders.shoot_probability,
+        )?;
+
+        for (field, weight) in [
+            (
+                "invaders.fire_weights.octopus",
+                self.invaders.fire_weights.octopus,
+            ),
+            (
+                "invaders.fire_weights.crab",
+                self.invaders.fire_weights.crab,
+            ),
+            (
+                "invaders.fire_weights.squid",
+                self.invaders.fire_weights.squid,
+            ),
+        ] {
+            if !(weight >= 0.0 && weight.is_finite()) {
+                return Err(invalid(&format!(
+                    "{field} must be 0 or greater (got {weight})"
+                )));
+            }
+        }
+
+        bullet_probabilities(
+            "invaders.bullet_probabilities",
+            &self.invaders.bullet_probabilities,
+        )
+    }
+}
+
+/// Parses the file at `path` with `options` and checks it with `validate`. When `required` is
+/// false a missing file gives `default()` instead of being an error.
+pub fn load_ron<T: DeserializeOwned>(
+    path: &Path,
+    required: bool,
+    options: ron::Options,
+    default: impl FnOnce() -> T,
+    validate: impl FnOnce(&T) -> Result<(), ConfigError>,
+) -> Result<T, ConfigError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound && !required => {
+            return Ok(default());
+        }
+        Err(error) => return Err(ConfigError::Io(error)),
+    };
+
+    let value = options.from_str(&contents).map_err(ConfigError::Parse)?;
+    validate(&value)?;
+
+    Ok(value)
+}
+
+fn invalid(message: &str) -> ConfigError {
+    ConfigError::Invalid(message.to_string())
+}
+
+pub fn positive(field: &str, value: f32) -> Result<(), ConfigError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(invalid(&format!(
+            "{field} must be greater than 0 (got {value})"
+        )))
+    }
+}
+
+pub fn probability(field: &str, value: f32) -> Result<(), ConfigError> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(invalid(&format!(
+            "{field} must be between 0.0 and 1.0 (got {value})"
+        )))
+    }
+}
+
+pub fn bullet_probabilities(
+    field: &str,
+    bullet_probabilities: &[InvaderBulletProbability],
+) -> Result<(), ConfigError> {
+    let mut total = 0.0;
+
+    for (i, bullet_probability) in bullet_probabilities.iter().enumerate() {
+        probability(
+            &format!("{field}[{i}].probability"),
+            bullet_probability.probability,
+        )?;
+        total += bullet_probability.probability;
+    }
+
+    if (total - 1.0).abs() > 0.001 {
+        return Err(invalid(&format!(
+            "{field} must add up to 1.0 (got {total})"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempFile;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(GameConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_names_the_offending_field() {
+        let mut config = GameConfig::default();
+        config.player.speed = -1.0;
+
+        match config.validate() {
+            Err(ConfigError::Invalid(message)) => assert!(message.contains("player.speed")),
+            _ => panic!("a negative player speed should be rejected"),
+        }
+    }
+
+    #[test]
+    fn validate_rejects_fastest_march_slower_than_slowest() {
+        let mut config = GameConfig::default();
+        config.timers.invader_move_fastest = config.timers.invader_move + 0.1;
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_an_empty_formation() {
+        let mut config = GameConfig::default();
+        config.invaders.decoy_rows = 0;
+        config.invaders.squid_rows = 0;
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_a_mystery_ship_worth_nothing() {
+        let mut config = GameConfig::default();
+        config.scoring.mystery_ship.clear();
+
+        assert!(config.validate().is_err());
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn march_interval_speeds_up_as_invaders_die() {
+        let config = GameConfig::default();
+        let slowest = config.timers.invader_move;
+        let fastest = config.timers.invader_move_fastest;
+
+        assert_close(config.march_interval(55, 55), slowest);
+        assert_close(config.march_interval(28, 55), (fastest + slowest) / 2.0);
+        assert_close(config.march_interval(1, 55), fastest);
+        assert_close(config.march_interval(1, 1), slowest);
+    }
+
+    #[test]
+    fn higher_march_curve_saves_the_speed_up_for_the_end() {
+        let mut config = GameConfig::default();
+        let linear = config.march_interval(28, 55);
+
+        config.invaders.march_curve = 2.0;
+        assert!(config.march_interval(28, 55) > linear);
+        assert_close(config.march_interval(55, 55), config.timers.invader_move);
+        assert_close(
+            config.march_interval(1, 55),
+            config.timers.invader_move_fastest,
+        );
+    }
+
+    #[test]
+    fn load_keeps_the_defaults_for_missing_fields() {
+        let file = TempFile::with_contents("config-partial.ron", "(player: (lives: 5))");
+        let config = GameConfig::load(file.path(), true).unwrap();
+
+        assert_eq!(config.player.lives, 5);
+        assert_eq!(config.player.speed, PlayerConfig::default().speed);
+    }
+
+    #[test]
+    fn load_rejects_unknown_fields() {
+        let file = TempFile::with_contents("config-unknown.ron", "(player: (livez: 5))");
+
+        assert!(matches!(
+            GameConfig::load(file.path(), true),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn load_falls_back_to_the_defaults_only_when_optional() {
+        let file = TempFile::new("config-missing.ron");
+
+        assert!(GameConfig::load(file.path(), false).is_ok());
+        assert!(matches!(
+            GameConfig::load(file.path(), true),
+            Err(ConfigError::Io(_))
+        ));
+    }
+}
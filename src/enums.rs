@@ -1,12 +1,178 @@
+use std::str::FromStr;
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Resource)]
+#[derive(Resource, Clone, Copy)]
 pub enum InvaderDirection {
     Left,
     Right,
 }
 
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum InvaderBulletType {
     Bullet,
     Bolt,
+    /// Drifts sideways towards the nearest player as it falls.
+    Homing,
+}
+
+/// What happens when a player shot meets an invader shot.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BulletClashOutcome {
+    BothDestroyed,
+    /// The invader shot survives and keeps falling.
+    PlayerShotAbsorbed,
+    /// The shots ignore each other.
+    PassThrough,
+}
+
+/// How the front invaders decide when to fire.
+#[derive(Resource, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum InvaderFireMode {
+    /// Every front invader rolls its own chance to fire.
+    #[default]
+    Random,
+    /// As [`InvaderFireMode::Random`], but the front invader nearest the player fires every time.
+    Aimed,
+}
+
+/// The three classic invaders, from the bottom of the formation to the top.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum InvaderType {
+    Octopus,
+    Crab,
+    Squid,
+}
+
+impl InvaderType {
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            'O' => Some(InvaderType::Octopus),
+            'C' => Some(InvaderType::Crab),
+            'S' => Some(InvaderType::Squid),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> char {
+        match self {
+            InvaderType::Octopus => 'O',
+            InvaderType::Crab => 'C',
+            InvaderType::Squid => 'S',
+        }
+    }
+
+    /// Sprite sheet holding the two frames of the invader's walk.
+    pub fn texture(&self) -> &'static str {
+        match self {
+            InvaderType::Octopus => "invader-1-sheet.png",
+            InvaderType::Crab => "invader-2-sheet.png",
+            InvaderType::Squid => "invader-3-sheet.png",
+        }
+    }
+}
+
+/// Something a player can do, whichever keys, buttons or stick it is bound to.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Pause,
+    Confirm,
+    /// Moves up through a menu.
+    Up,
+    /// Moves down through a menu.
+    Down,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Fire,
+        Action::Pause,
+        Action::Confirm,
+        Action::Up,
+        Action::Down,
+    ];
+}
+
+/// How many people play and how they share the game.
+#[derive(
+    Resource, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug, Serialize, Deserialize,
+)]
+pub enum GameMode {
+    #[default]
+    Single,
+    /// Two players on one keyboard, fighting the same invaders at the same time.
+    CoOp,
+    /// Two players taking turns, each with their own waves and shields. The turn passes
+    /// whenever the player in play loses a life.
+    Alternating,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Single, GameMode::CoOp, GameMode::Alternating];
+
+    pub fn player_count(&self) -> u32 {
+        match self {
+            GameMode::Single => 1,
+            GameMode::CoOp | GameMode::Alternating => 2,
+        }
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "single" => Ok(GameMode::Single),
+            "co-op" => Ok(GameMode::CoOp),
+            "alternating" => Ok(GameMode::Alternating),
+            _ => Err(format!("unknown game mode '{mode}'")),
+        }
+    }
+}
+
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    #[default]
+    Title,
+    Options,
+    HighScores,
+    /// Spawns a new game's players, shields and invaders, then moves on to `Playing`.
+    Starting,
+    Playing,
+    PlayerHit,
+    RespawningInvaders,
+    /// A short wait after a new wave is spawned before it starts moving.
+    WaveDelay,
+    GameOver,
+}
+
+impl GameState {
+    /// Whether a game is under way and can be paused.
+    pub fn is_in_game(&self) -> bool {
+        matches!(
+            self,
+            GameState::Playing
+                | GameState::PlayerHit
+                | GameState::RespawningInvaders
+                | GameState::WaveDelay
+        )
+    }
+}
+
+/// Whether a player has frozen the game. Kept apart from [`GameState`] so pausing neither
+/// leaves nor re-enters the state the game was in.
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+    /// The options screen, opened from the pause menu.
+    Options,
 }
@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use bevy::{
+    app::{AppExit, ScheduleRunnerPlugin},
+    input::{InputPlugin, InputSystem},
+    log::LogPlugin,
+    prelude::*,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
+
+use crate::{
+    enums::Action,
+    input::{ActionInput, ActionSystem},
+};
+
+pub const HEADLESS_TIMESTEP: f64 = 1.0 / 60.0;
+pub const DEFAULT_HEADLESS_FRAMES: u32 = 3600;
+
+/// Supplies the actions each player holds on each simulated frame, first player first.
+pub trait InputSource: Send + Sync + 'static {
+    fn pressed_actions(&mut self, frame: u32) -> Vec<Vec<Action>>;
+}
+
+impl<F> InputSource for F
+where
+    F: FnMut(u32) -> Vec<Vec<Action>> + Send + Sync + 'static,
+{
+    fn pressed_actions(&mut self, frame: u32) -> Vec<Vec<Action>> {
+        self(frame)
+    }
+}
+
+/// Sweeps the players back and forth across the screen while firing at a steady rate. The
+/// second player mirrors the first and fires half a beat later.
+pub struct Autopilot;
+
+impl InputSource for Autopilot {
+    fn pressed_actions(&mut self, frame: u32) -> Vec<Vec<Action>> {
+        let (mut first, mut second) = if (frame / 120).is_multiple_of(2) {
+            (vec![Action::MoveRight], vec![Action::MoveLeft])
+        } else {
+            (vec![Action::MoveLeft], vec![Action::MoveRight])
+        };
+
+        if frame.is_multiple_of(30) {
+            first.push(Action::Fire);
+        }
+
+        if frame % 30 == 15 {
+            second.push(Action::Fire);
+        }
+
+        vec![first, second]
+    }
+}
+
+/// Actions fed to the game in place of the players' bindings. Insert this alongside
+/// [`HeadlessPlugin`] to script what the players do.
+#[derive(Resource)]
+pub struct InjectedInput {
+    pub source: Box<dyn InputSource>,
+    pub frame: u32,
+    previous: Vec<Vec<Action>>,
+}
+
+impl InjectedInput {
+    pub fn new(source: impl InputSource) -> Self {
+        InjectedInput {
+            source: Box::new(source),
+            frame: 0,
+            previous: Vec::new(),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct HeadlessFrameLimit(pub u32);
+
+/// How many frames the game has simulated, which stops it once it reaches the frame limit.
+#[derive(Resource, Default)]
+pub struct SimulatedFrames(pub u32);
+
+/// Feeds the [`InjectedInput`] to the game, one of the source's frames each time it runs.
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct InjectedInputSystem;
+
+/// Runs the game without a window or GPU: time advances by a fixed step every update and
+/// the players' actions are read from an [`InputSource`] instead of the keyboard.
+pub struct HeadlessPlugin {
+    pub frames: u32,
+    pub timestep: Duration,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+            LogPlugin::default(),
+            TransformPlugin,
+            HierarchyPlugin,
+            InputPlugin,
+            StatesPlugin,
+            AssetPlugin::default(),
+            ImagePlugin::default_nearest(),
+        ))
+        .init_asset::<TextureAtlasLayout>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(self.timestep))
+        .insert_resource(HeadlessFrameLimit(self.frames))
+        .init_resource::<SimulatedFrames>()
+        .add_systems(
+            PreUpdate,
+            apply_injected_input
+                .in_set(InjectedInputSystem)
+                .after(InputSystem)
+                .after(ActionSystem)
+                .run_if(resource_exists::<InjectedInput>),
+        )
+        .add_systems(PostUpdate, stop_after_frame_limit);
+    }
+}
+
+pub fn apply_injected_input(mut injected: ResMut<InjectedInput>, mut actions: ResMut<ActionInput>) {
+    let frame = injected.frame;
+    let current = injected.source.pressed_actions(frame);
+    injected.frame += 1;
+
+    actions.set_pressed(&injected.previous, &current);
+    injected.previous = current;
+}
+
+/// Counts the frames simulated rather than reading [`FrameCount`](bevy::core::FrameCount), as
+/// netplay skips the frame on updates spent waiting for the other player.
+fn stop_after_frame_limit(
+    mut frames: ResMut<SimulatedFrames>,
+    limit: Res<HeadlessFrameLimit>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if frames.0 >= limit.0 {
+        exit.send(AppExit::Success);
+    } else {
+        frames.0 += 1;
+    }
+}
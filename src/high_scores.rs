@@ -0,0 +1,400 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::Player,
+    config::ConfigError,
+    enums::{Action, GameMode, GameState},
+    input::ActionInput,
+};
+
+/// Saved under this directory inside the user's data directory.
+const DATA_DIR_NAME: &str = "space-invaders-boogaloo";
+const HIGH_SCORES_FILE: &str = "high-scores.ron";
+
+/// How many scores each table keeps.
+const TABLE_SIZE: usize = 10;
+const INITIALS_LENGTH: usize = 3;
+
+const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub initials: String,
+    pub score: u32,
+}
+
+/// The best scores of each game mode, best first.
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighScores {
+    pub tables: BTreeMap<GameMode, Vec<HighScore>>,
+}
+
+impl HighScores {
+    /// Reads the table at `path`. A missing file means no scores yet. Tables are put back in
+    /// order and cut down to size in case the file was edited by hand.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(HighScores::default());
+            }
+            Err(error) => return Err(ConfigError::Io(error)),
+        };
+
+        let mut scores: HighScores = ron::from_str(&contents).map_err(ConfigError::Parse)?;
+
+        for (mode, table) in scores.tables.iter_mut() {
+            if let Some(entry) = table.iter().find(|entry| !valid_initials(&entry.initials)) {
+                return Err(ConfigError::Invalid(format!(
+                    "{mode:?} table has initials \"{}\" (expected {INITIALS_LENGTH} letters A-Z)",
+                    entry.initials
+                )));
+            }
+
+            table.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+            table.truncate(TABLE_SIZE);
+        }
+
+        Ok(scores)
+    }
+
+    /// Writes the table to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(io::Error::other)?;
+
+        fs::write(path, contents)
+    }
+
+    pub fn table(&self, mode: GameMode) -> &[HighScore] {
+        self.tables.get(&mode).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn best(&self, mode: GameMode) -> u32 {
+        self.table(mode).first().map_or(0, |entry| entry.score)
+    }
+
+    /// Whether `score` would make it onto the mode's table.
+    pub fn qualifies(&self, mode: GameMode, score: u32) -> bool {
+        let table = self.table(mode);
+
+        score > 0
+            && (table.len() < TABLE_SIZE || table.last().is_some_and(|last| score > last.score))
+    }
+
+    /// Adds `entry` below any equal scores already on the table, dropping whatever falls off
+    /// the bottom.
+    pub fn insert(&mut self, mode: GameMode, entry: HighScore) {
+        let table = self.tables.entry(mode).or_default();
+        let index = table.partition_point(|existing| existing.score >= entry.score);
+
+        table.insert(index, entry);
+        table.truncate(TABLE_SIZE);
+    }
+}
+
+fn valid_initials(initials: &str) -> bool {
+    initials.len() == INITIALS_LENGTH && initials.bytes().all(|letter| LETTERS.contains(&letter))
+}
+
+/// `high-scores.ron` in the user's data directory, if the platform has one.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|directory| directory.join(DATA_DIR_NAME).join(HIGH_SCORES_FILE))
+}
+
+/// Where the high scores are saved. Missing when the table must not be written, such as when
+/// playing back a replay.
+#[derive(Resource)]
+pub struct HighScorePath(pub PathBuf);
+
+/// Players typing their initials into the table on the game over screen, one at a time.
+#[derive(Resource)]
+pub struct InitialsEntry {
+    /// Each player still to enter initials with their score, the one entering first.
+    pending: Vec<(u32, u32)>,
+    initials: String,
+    letter: usize,
+}
+
+#[derive(Component)]
+pub struct InitialsText {}
+
+/// Keeps the high-score tables and asks for initials when a game ends on a score that makes
+/// the table.
+pub struct HighScorePlugin {
+    pub scores: HighScores,
+    pub path: Option<PathBuf>,
+}
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.scores.clone())
+            .add_systems(OnEnter(GameState::GameOver), begin_initials_entry)
+            .add_systems(
+                Update,
+                (enter_initials, show_initials)
+                    .chain()
+                    .run_if(in_state(GameState::GameOver))
+                    .distributive_run_if(entering_initials),
+            );
+
+        if let Some(path) = &self.path {
+            app.insert_resource(HighScorePath(path.clone()));
+        }
+    }
+}
+
+pub fn entering_initials(entry: Option<Res<InitialsEntry>>) -> bool {
+    entry.is_some()
+}
+
+fn begin_initials_entry(
+    mut commands: Commands,
+    players: Query<&Player>,
+    scores: Res<HighScores>,
+    mode: Res<GameMode>,
+) {
+    let mut pending: Vec<(u32, u32)> = players
+        .iter()
+        .filter(|player| scores.qualifies(*mode, player.score))
+        .map(|player| (player.number, player.score))
+        .collect();
+
+    if pending.is_empty() {
+        return;
+    }
+
+    pending.sort();
+
+    commands.insert_resource(InitialsEntry {
+        pending,
+        initials: String::new(),
+        letter: 0,
+    });
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: Default::default(),
+                font_size: 30.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(65.0),
+            width: Val::Percent(100.0),
+            ..Default::default()
+        }),
+        InitialsText {},
+    ));
+}
+
+/// Up and right step to the next letter, down and left to the previous one. Fire or confirm
+/// locks the letter in and pause takes the last one back.
+pub fn enter_initials(
+    mut commands: Commands,
+    mut entry: ResMut<InitialsEntry>,
+    actions: Res<ActionInput>,
+    mut scores: ResMut<HighScores>,
+    mode: Res<GameMode>,
+    path: Option<Res<HighScorePath>>,
+    texts: Query<Entity, With<InitialsText>>,
+) {
+    let (number, score) = entry.pending[0];
+    let input = actions.player(number);
+
+    if input.just_pressed(Action::Up) || input.just_pressed(Action::MoveRight) {
+        entry.letter = (entry.letter + 1) % LETTERS.len();
+    }
+
+    if input.just_pressed(Action::Down) || input.just_pressed(Action::MoveLeft) {
+        entry.letter = (entry.letter + LETTERS.len() - 1) % LETTERS.len();
+    }
+
+    if input.just_pressed(Action::Pause) {
+        entry.initials.pop();
+    }
+
+    if !input.just_pressed(Action::Confirm) && !input.just_pressed(Action::Fire) {
+        return;
+    }
+
+    let letter = LETTERS[entry.letter] as char;
+    entry.initials.push(letter);
+    entry.letter = 0;
+
+    if entry.initials.len() < INITIALS_LENGTH {
+        return;
+    }
+
+    let initials = std::mem::take(&mut entry.initials);
+    scores.insert(*mode, HighScore { initials, score });
+
+    if let Some(path) = path {
+        if let Err(error) = scores.save(&path.0) {
+            error!(
+                "Could not save high scores to {}: {}",
+                path.0.display(),
+                error
+            );
+        }
+    }
+
+    entry.pending.remove(0);
+    entry
+        .pending
+        .retain(|(_, score)| scores.qualifies(*mode, *score));
+
+    if entry.pending.is_empty() {
+        commands.remove_resource::<InitialsEntry>();
+
+        for text in texts.iter() {
+            commands.entity(text).despawn();
+        }
+    }
+}
+
+fn show_initials(entry: Res<InitialsEntry>, mut texts: Query<&mut Text, With<InitialsText>>) {
+    let Some((number, _)) = entry.pending.first() else {
+        return;
+    };
+
+    let mut letters: Vec<char> = entry.initials.chars().collect();
+    letters.push(LETTERS[entry.letter] as char);
+    letters.resize(INITIALS_LENGTH, '_');
+
+    let letters: Vec<String> = letters.iter().map(char::to_string).collect();
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!(
+            "PLAYER {number} - NEW HIGH SCORE!\nENTER YOUR INITIALS\n{}",
+            letters.join(" ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempFile;
+
+    fn entry(initials: &str, score: u32) -> HighScore {
+        HighScore {
+            initials: initials.to_string(),
+            score,
+        }
+    }
+
+    fn full_table() -> HighScores {
+        let mut scores = HighScores::default();
+
+        for score in 1..=TABLE_SIZE as u32 {
+            scores.insert(GameMode::Single, entry("AAA", score * 100));
+        }
+
+        scores
+    }
+
+    #[test]
+    fn insert_keeps_the_table_best_first() {
+        let mut scores = HighScores::default();
+        scores.insert(GameMode::Single, entry("AAA", 100));
+        scores.insert(GameMode::Single, entry("BBB", 300));
+        scores.insert(GameMode::Single, entry("CCC", 100));
+
+        let table: Vec<(&str, u32)> = scores
+            .table(GameMode::Single)
+            .iter()
+            .map(|entry| (entry.initials.as_str(), entry.score))
+            .collect();
+
+        assert_eq!(table, [("BBB", 300), ("AAA", 100), ("CCC", 100)]);
+        assert_eq!(scores.best(GameMode::Single), 300);
+        assert_eq!(scores.best(GameMode::CoOp), 0);
+    }
+
+    #[test]
+    fn insert_drops_whatever_falls_off_the_bottom() {
+        let mut scores = full_table();
+        scores.insert(GameMode::Single, entry("ZZZ", 150));
+
+        let table = scores.table(GameMode::Single);
+        assert_eq!(table.len(), TABLE_SIZE);
+        assert_eq!(table.last().unwrap().score, 150);
+    }
+
+    #[test]
+    fn qualifies_needs_a_free_place_or_a_better_score() {
+        let scores = full_table();
+
+        assert!(!scores.qualifies(GameMode::Single, 100));
+        assert!(scores.qualifies(GameMode::Single, 101));
+        assert!(scores.qualifies(GameMode::CoOp, 1));
+        assert!(!scores.qualifies(GameMode::CoOp, 0));
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let file = TempFile::new("high-scores-round-trip.ron");
+        full_table().save(file.path()).unwrap();
+        let loaded = HighScores::load(file.path()).unwrap();
+
+        assert_eq!(loaded.best(GameMode::Single), 1000);
+        assert_eq!(loaded.table(GameMode::Single).len(), TABLE_SIZE);
+    }
+
+    #[test]
+    fn load_sorts_and_truncates_edited_tables() {
+        let file = TempFile::new("high-scores-edited.ron");
+        let mut scores = HighScores::default();
+        scores.tables.insert(
+            GameMode::Alternating,
+            (0..15).map(|score| entry("ABC", score)).collect(),
+        );
+        scores.save(file.path()).unwrap();
+        let loaded = HighScores::load(file.path()).unwrap();
+
+        let table = loaded.table(GameMode::Alternating);
+        assert_eq!(table.len(), TABLE_SIZE);
+        assert_eq!(table[0].score, 14);
+        assert_eq!(table[TABLE_SIZE - 1].score, 5);
+    }
+
+    #[test]
+    fn load_rejects_bad_initials() {
+        let file = TempFile::new("high-scores-bad-initials.ron");
+        let mut scores = HighScores::default();
+        scores
+            .tables
+            .insert(GameMode::Single, vec![entry("abcd", 10)]);
+        scores.save(file.path()).unwrap();
+
+        assert!(matches!(
+            HighScores::load(file.path()),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn missing_file_means_no_scores() {
+        let file = TempFile::new("high-scores-missing.ron");
+        let loaded = HighScores::load(file.path()).unwrap();
+
+        assert!(loaded.tables.is_empty());
+    }
+}
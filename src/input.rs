@@ -0,0 +1,281 @@
+use std::{collections::BTreeMap, hash::Hash, path::Path};
+
+use bevy::{
+    input::{
+        gamepad::{GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType, Gamepads},
+        InputSystem,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{load_ron, probability, ConfigError},
+    enums::Action,
+};
+
+/// Loaded when no `--bindings` flag is given. A missing file here means the default bindings.
+pub const DEFAULT_BINDINGS_PATH: &str = "bindings.ron";
+
+pub const MAX_PLAYERS: usize = 2;
+
+/// The keys, gamepad buttons and stick behind each player's actions, first player first.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bindings {
+    pub players: Vec<PlayerBindings>,
+    /// How far a stick has to be pushed, from 0 to 1, before it moves the player.
+    pub dead_zone: f32,
+}
+
+/// One player's bindings. Actions left out are not bound to anything.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerBindings {
+    pub keys: BTreeMap<Action, Vec<KeyCode>>,
+    /// Which gamepad the player uses, counting connected gamepads from 0.
+    pub gamepad: Option<usize>,
+    pub buttons: BTreeMap<Action, Vec<GamepadButtonType>>,
+    /// Moves the player left when pushed below the dead zone and right when pushed above it.
+    pub move_axis: Option<GamepadAxisType>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            players: vec![
+                PlayerBindings {
+                    keys: BTreeMap::from([
+                        (Action::MoveLeft, vec![KeyCode::ArrowLeft]),
+                        (Action::MoveRight, vec![KeyCode::ArrowRight]),
+                        (Action::Fire, vec![KeyCode::Space]),
+                        (Action::Pause, vec![KeyCode::Escape]),
+                        (Action::Confirm, vec![KeyCode::Enter]),
+                        (Action::Up, vec![KeyCode::ArrowUp]),
+                        (Action::Down, vec![KeyCode::ArrowDown]),
+                    ]),
+                    gamepad: Some(0),
+                    ..PlayerBindings::gamepad_defaults()
+                },
+                PlayerBindings {
+                    keys: BTreeMap::from([
+                        (Action::MoveLeft, vec![KeyCode::KeyA]),
+                        (Action::MoveRight, vec![KeyCode::KeyD]),
+                        (Action::Fire, vec![KeyCode::KeyW]),
+                    ]),
+                    gamepad: Some(1),
+                    ..PlayerBindings::gamepad_defaults()
+                },
+            ],
+            dead_zone: 0.5,
+        }
+    }
+}
+
+impl PlayerBindings {
+    fn gamepad_defaults() -> Self {
+        PlayerBindings {
+            keys: BTreeMap::new(),
+            gamepad: None,
+            buttons: BTreeMap::from([
+                (Action::MoveLeft, vec![GamepadButtonType::DPadLeft]),
+                (Action::MoveRight, vec![GamepadButtonType::DPadRight]),
+                (Action::Fire, vec![GamepadButtonType::South]),
+                (Action::Pause, vec![GamepadButtonType::Start]),
+                (Action::Confirm, vec![GamepadButtonType::South]),
+                (Action::Up, vec![GamepadButtonType::DPadUp]),
+                (Action::Down, vec![GamepadButtonType::DPadDown]),
+            ]),
+            move_axis: Some(GamepadAxisType::LeftStickX),
+        }
+    }
+
+    fn pressed(
+        &self,
+        action: Action,
+        keys: &ButtonInput<KeyCode>,
+        gamepad: Option<Gamepad>,
+        buttons: &ButtonInput<GamepadButton>,
+        axes: &Axis<GamepadAxis>,
+        dead_zone: f32,
+    ) -> bool {
+        let key_pressed = self
+            .keys
+            .get(&action)
+            .is_some_and(|bound| keys.any_pressed(bound.iter().copied()));
+
+        let Some(gamepad) = gamepad else {
+            return key_pressed;
+        };
+
+        let button_pressed = self.buttons.get(&action).is_some_and(|bound| {
+            buttons.any_pressed(
+                bound
+                    .iter()
+                    .map(|button| GamepadButton::new(gamepad, *button)),
+            )
+        });
+
+        let stick = self
+            .move_axis
+            .and_then(|axis| axes.get(GamepadAxis::new(gamepad, axis)))
+            .unwrap_or(0.0);
+
+        let stick_pressed = match action {
+            Action::MoveLeft => stick < -dead_zone,
+            Action::MoveRight => stick > dead_zone,
+            _ => false,
+        };
+
+        key_pressed || button_pressed || stick_pressed
+    }
+}
+
+impl Bindings {
+    /// Reads the bindings at `path` through [`load_ron`], falling back to the default keys.
+    pub fn load(path: &Path, required: bool) -> Result<Self, ConfigError> {
+        load_ron(
+            path,
+            required,
+            ron::Options::default(),
+            Bindings::default,
+            Bindings::validate,
+        )
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.players.len() > MAX_PLAYERS {
+            return Err(ConfigError::Invalid(format!(
+                "players lists {} players but there are at most {MAX_PLAYERS}",
+                self.players.len()
+            )));
+        }
+
+        probability("dead_zone", self.dead_zone)
+    }
+}
+
+/// Each player's actions this frame. They are read from the bindings, then may be replaced by
+/// a replay, the headless autopilot or the other peer of a network game before gameplay sees
+/// them.
+#[derive(Resource, Default)]
+pub struct ActionInput {
+    players: [ButtonInput<Action>; MAX_PLAYERS],
+}
+
+impl ActionInput {
+    pub fn player(&self, number: u32) -> &ButtonInput<Action> {
+        &self.players[number as usize - 1]
+    }
+
+    /// Presses `action` for the player as if it had been read from their bindings.
+    pub fn press(&mut self, number: u32, action: Action) {
+        self.players[number as usize - 1].press(action);
+    }
+
+    pub fn any_just_pressed(&self, action: Action) -> bool {
+        self.players
+            .iter()
+            .any(|player| player.just_pressed(action))
+    }
+
+    /// The actions each player is holding, first player first.
+    pub fn pressed(&self) -> Vec<Vec<Action>> {
+        self.players
+            .iter()
+            .map(|player| {
+                let mut pressed: Vec<Action> = player.get_pressed().copied().collect();
+                pressed.sort();
+                pressed
+            })
+            .collect()
+    }
+
+    /// Replaces every player's actions with `current`, comparing with the `previous` frame's
+    /// to tell which were just pressed or released.
+    pub fn set_pressed(&mut self, previous: &[Vec<Action>], current: &[Vec<Action>]) {
+        for (index, player) in self.players.iter_mut().enumerate() {
+            set_pressed(
+                player,
+                previous.get(index).map_or(&[], Vec::as_slice),
+                current.get(index).map_or(&[], Vec::as_slice),
+            );
+        }
+    }
+}
+
+/// Replaces the state of `input` with `current`, marking buttons as just pressed or just
+/// released by comparing with the `previous` frame's.
+pub fn set_pressed<T>(input: &mut ButtonInput<T>, previous: &[T], current: &[T])
+where
+    T: Copy + Eq + Hash + Send + Sync + 'static,
+{
+    input.reset_all();
+
+    for button in current {
+        input.press(*button);
+
+        if previous.contains(button) {
+            input.clear_just_pressed(*button);
+        }
+    }
+
+    for button in previous.iter().filter(|button| !current.contains(button)) {
+        input.press(*button);
+        input.release(*button);
+        input.clear_just_pressed(*button);
+    }
+}
+
+/// Runs once every player's actions have been read from their bindings. Anything that replaces
+/// the actions runs after it.
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ActionSystem;
+
+/// Turns keyboard and gamepad input into each player's actions through the [`Bindings`].
+pub struct ActionPlugin {
+    pub bindings: Bindings,
+}
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.bindings.clone())
+            .init_resource::<ActionInput>()
+            .add_systems(
+                PreUpdate,
+                read_bindings.in_set(ActionSystem).after(InputSystem),
+            );
+    }
+}
+
+pub fn read_bindings(
+    bindings: Res<Bindings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<ActionInput>,
+) {
+    let mut connected: Vec<Gamepad> = gamepads.iter().collect();
+    connected.sort_by_key(|gamepad| gamepad.id);
+
+    let current: Vec<Vec<Action>> = bindings
+        .players
+        .iter()
+        .map(|player| {
+            let gamepad = player
+                .gamepad
+                .and_then(|index| connected.get(index).copied());
+
+            Action::ALL
+                .into_iter()
+                .filter(|action| {
+                    player.pressed(*action, &keys, gamepad, &buttons, &axes, bindings.dead_zone)
+                })
+                .collect()
+        })
+        .collect();
+
+    let previous = actions.pressed();
+    actions.set_pressed(&previous, &current);
+}
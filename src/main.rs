@@ -1,71 +1,285 @@
+mod cli;
+mod collision;
 mod components;
+mod config;
 mod enums;
+mod headless;
+mod high_scores;
+mod input;
+mod menu;
+mod netplay;
+mod replay;
 mod resources;
-
-use bevy::{prelude::*, render::camera::ScalingMode, window::EnabledButtons};
+mod spatial_hash;
+#[cfg(test)]
+mod test_support;
+mod waves;
+
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::ScalingMode,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    window::EnabledButtons,
+};
+use cli::{exit_with_error, CliArgs};
+use collision::{
+    detect_collisions, rebuild_broadphase, Broadphase, Collider, CollisionEvent, CollisionLayers,
+    SpentColliders, Swept,
+};
 use components::{
-    Bullet, GameOverText, GameRestartButton, GameRestartText, Invader, InvaderBullet,
-    InvaderBulletProbability, Player, PlayerBullet, PlayerID, PlayerLife, PlayerScore, ShieldPart,
-    Shooter,
+    AwaitingTurn, Bullet, GameOverText, GameRestartButton, GameRestartText, HighScoreText, Invader,
+    InvaderBullet, MysteryShip, Player, PlayerBullet, PlayerID, PlayerLife, PlayerLivesText,
+    PlayerScore, PlayerShootTimer, Shield, Shooter, SpriteAnimation,
 };
-use enums::{InvaderBulletType, InvaderDirection};
+use config::{GameConfig, DEFAULT_CONFIG_PATH};
+use enums::{
+    Action, BulletClashOutcome, GameMode, GameState, InvaderBulletType, InvaderDirection,
+    InvaderFireMode, PauseState,
+};
+use headless::{
+    Autopilot, HeadlessPlugin, InjectedInput, SimulatedFrames, DEFAULT_HEADLESS_FRAMES,
+    HEADLESS_TIMESTEP,
+};
+use high_scores::{enter_initials, entering_initials, HighScorePlugin, HighScores};
+use input::{ActionInput, ActionPlugin, Bindings, DEFAULT_BINDINGS_PATH};
+use menu::MenuPlugin;
+use netplay::{NetSession, NetShim, NetplayPlugin, SharedGame};
 use rand::Rng;
+use replay::{Replay, ReplayMode, ReplayPlugin};
 use resources::{
-    GameState, InvaderShootTimer, InvaderSpeed, InvaderTimer, PlayerHitAnimationTimer,
-    PlayerHitTimer, PlayerShootTimer, RespawningInvadersTimer,
+    GameRng, InvaderBulletProbabilities, InvaderShootProbability, InvaderShootTimer,
+    InvaderSnapshot, InvaderSpeed, InvaderTimer, MarchTempo, MysteryShipTimer,
+    PlayerHitAnimationTimer, PlayerHitTimer, PlayerSnapshot, RespawningInvadersTimer, RngSeed,
+    SpriteSheets, TurnSnapshots,
 };
-
-const WINDOW_WIDTH: f32 = 640.0;
-const WINDOW_HEIGHT: f32 = 480.0;
+use waves::{CurrentWave, WaveDefinition, Waves, DEFAULT_WAVES_PATH};
 
 const SHIELD_NUMS: usize = 4;
+const SHIELD_COLOR: [u8; 4] = [0, 233, 43, 255];
+
+const SHIELD_SHAPE: &[&str] = &[
+    "...##################...",
+    "..####################..",
+    ".######################.",
+    "########################",
+    "########################",
+    "########################",
+    "########################",
+    "########################",
+    "##########....##########",
+    "#########......#########",
+    "########........########",
+    "#######..........#######",
+    "######............######",
+    "######............######",
+    "######............######",
+    "######............######",
+    "######............######",
+    "######............######",
+];
 
-const INVADER_SPEED: f32 = 250.0;
-const INVADER_COLS: usize = 11;
-const INVADER_DECOY_ROWS: usize = 2;
-const INVADER_SHOOTER_ROWS: usize = 1;
-const INVADER_SHOOT_PROBABILITY: f32 = 0.10;
+/// The holes blown out of a shield by each kind of shot, centred on the point of impact.
+const PLAYER_BULLET_EXPLOSION: &[&str] = &[
+    "#...#..#", //
+    "..#...#.", //
+    ".######.", //
+    "########", //
+    "########", //
+    ".######.", //
+    "..#..#..", //
+    "#..#...#", //
+];
 
-const INVADER_BULLET_PROBABILITIES: &'static [InvaderBulletProbability] = &[
-    InvaderBulletProbability {
-        bullet_type: InvaderBulletType::Bullet,
-        probability: 0.75,
-    },
-    InvaderBulletProbability {
-        bullet_type: InvaderBulletType::Bolt,
-        probability: 0.25,
-    },
+const INVADER_BULLET_EXPLOSION: &[&str] = &[
+    "..#...", //
+    "#...#.", //
+    "..##.#", //
+    ".####.", //
+    "#.###.", //
+    ".#####", //
+    "#.###.", //
+    ".#.#.#", //
 ];
 
+const MYSTERY_SHIP_HEIGHT: f32 = 84.0;
+
+/// Where each player starts, from the first player to the second.
+const PLAYER_START_X: [f32; 2] = [-77.75, 77.75];
+
 // TODO: Refactor magic numbers and update enemy bullets, then add special enemy bullets, rare enemies, power-ups, and bosses
-fn main() {
-    App::new()
-        .insert_resource(PlayerShootTimer(Timer::from_seconds(0.5, TimerMode::Once)))
-        .insert_resource(InvaderTimer(Timer::from_seconds(
-            0.85,
-            TimerMode::Repeating,
-        )))
-        .insert_resource(InvaderShootTimer(Timer::from_seconds(
-            1.0,
-            TimerMode::Repeating,
-        )))
-        .insert_resource(GameState::Playing)
-        .insert_resource(PlayerHitTimer(Timer::from_seconds(1.5, TimerMode::Once)))
-        .insert_resource(PlayerHitAnimationTimer(Timer::from_seconds(
-            0.1,
-            TimerMode::Repeating,
-        )))
-        .insert_resource(RespawningInvadersTimer(Timer::from_seconds(
-            1.0,
-            TimerMode::Once,
-        )))
-        .add_plugins(
+fn main() -> AppExit {
+    let args = CliArgs::parse();
+
+    let replay = args.replay.as_ref().map(|path| {
+        Replay::load(path).unwrap_or_else(|error| {
+            exit_with_error(&format!("could not load {}: {}", path.display(), error))
+        })
+    });
+
+    let config = match &replay {
+        Some(replay) => replay.config.clone(),
+        None => {
+            let (path, required) = match &args.config {
+                Some(path) => (path.clone(), true),
+                None => (PathBuf::from(DEFAULT_CONFIG_PATH), false),
+            };
+
+            GameConfig::load(&path, required).unwrap_or_else(|error| {
+                exit_with_error(&format!("could not load {}: {}", path.display(), error))
+            })
+        }
+    };
+
+    let waves = match &replay {
+        Some(replay) => replay.waves.clone(),
+        None => {
+            let (path, required) = match &args.waves {
+                Some(path) => (path.clone(), true),
+                None => (PathBuf::from(DEFAULT_WAVES_PATH), false),
+            };
+
+            Waves::load(&path, required, &config).unwrap_or_else(|error| {
+                exit_with_error(&format!("could not load {}: {}", path.display(), error))
+            })
+        }
+    };
+
+    let seed = match &replay {
+        Some(replay) => replay.seed,
+        None => args
+            .seed
+            .or(config.seed)
+            .unwrap_or_else(|| rand::thread_rng().gen()),
+    };
+
+    let bindings = {
+        let (path, required) = match &args.bindings {
+            Some(path) => (path.clone(), true),
+            None => (PathBuf::from(DEFAULT_BINDINGS_PATH), false),
+        };
+
+        Bindings::load(&path, required).unwrap_or_else(|error| {
+            exit_with_error(&format!("could not load {}: {}", path.display(), error))
+        })
+    };
+
+    // Logged once the app has set up logging.
+    let mut high_scores_warning = None;
+
+    let (high_scores, high_scores_path) = match &replay {
+        Some(replay) => (replay.high_scores.clone(), None),
+        None => {
+            // Headless runs only touch a high-score file when given one.
+            let path = match &args.high_scores {
+                Some(path) => Some(path.clone()),
+                None if args.headless => None,
+                None => high_scores::default_path(),
+            };
+
+            match path {
+                Some(path) => match HighScores::load(&path) {
+                    Ok(high_scores) => (high_scores, Some(path)),
+                    Err(error) if args.high_scores.is_some() => {
+                        exit_with_error(&format!("could not load {}: {}", path.display(), error))
+                    }
+                    Err(error) => {
+                        high_scores_warning = Some(format!(
+                            "Could not load {}, so high scores will not be saved: {}",
+                            path.display(),
+                            error
+                        ));
+                        (HighScores::default(), None)
+                    }
+                },
+                None => (HighScores::default(), None),
+            }
+        }
+    };
+
+    let shim = NetShim::new(
+        Duration::from_millis(args.net_latency.unwrap_or(0)),
+        args.net_loss.unwrap_or(0.0) / 100.0,
+    );
+
+    let (game, net_session) = match (args.host, args.join) {
+        (Some(port), _) => {
+            let game = SharedGame {
+                seed,
+                config,
+                waves,
+                high_scores,
+            };
+            let session = NetSession::host(port, shim, &game).unwrap_or_else(|error| {
+                exit_with_error(&format!("could not host on port {port}: {error}"))
+            });
+
+            (game, Some(session))
+        }
+        (_, Some(address)) => {
+            let (session, game) = NetSession::join(address, shim).unwrap_or_else(|error| {
+                exit_with_error(&format!("could not join {address}: {error}"))
+            });
+
+            (game, Some(session))
+        }
+        _ => (
+            SharedGame {
+                seed,
+                config,
+                waves,
+                high_scores,
+            },
+            None,
+        ),
+    };
+    let SharedGame {
+        seed,
+        config,
+        waves,
+        high_scores,
+    } = game;
+
+    // The joining peer plays with the host's table, which is the host's to save.
+    let high_scores_path = high_scores_path.filter(|_| args.join.is_none());
+
+    // Without a mode the game opens on the title screen, where the players pick one.
+    let mode = match (&replay, &net_session) {
+        (Some(replay), _) => replay.mode,
+        (_, Some(_)) => Some(GameMode::CoOp),
+        _ if args.headless => Some(args.mode.unwrap_or_default()),
+        _ => args.mode,
+    };
+
+    let mut app = App::new();
+
+    if args.headless {
+        let frames = match &replay {
+            Some(replay) => args.frames.unwrap_or(replay.frames.len() as u32 + 1),
+            None => args.frames.unwrap_or(DEFAULT_HEADLESS_FRAMES),
+        };
+
+        app.add_plugins(HeadlessPlugin {
+            frames,
+            timestep: Duration::from_secs_f64(HEADLESS_TIMESTEP),
+        })
+        .add_systems(Last, log_simulation_summary.run_if(on_event::<AppExit>()));
+
+        if replay.is_none() {
+            app.insert_resource(InjectedInput::new(Autopilot));
+        }
+    } else {
+        app.add_plugins(
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
                 .set(WindowPlugin {
                     primary_window: Some(Window {
                         title: "Space Invaders".into(),
-                        resolution: (WINDOW_WIDTH, WINDOW_HEIGHT).into(),
+                        resolution: (config.window.width, config.window.height).into(),
                         resizable: false,
                         position: WindowPosition::Centered(MonitorSelection::Primary),
                         enabled_buttons: EnabledButtons {
@@ -78,61 +292,223 @@ fn main() {
                     ..Default::default()
                 })
                 .build(),
-        )
-        .add_systems(
-            Startup,
-            (
-                setup_camera,
-                (setup_player, setup_player_score, setup_player_lives).chain(),
-                setup_shields,
-                setup_invaders,
-            ),
-        )
-        .add_systems(
-            Update,
-            (
-                players_movement,
-                invaders_movement,
-                spawn_player_bullets,
-                invaders_shooting,
+        );
+    }
+
+    app.add_plugins(ActionPlugin { bindings });
+
+    if let Some(replay) = replay {
+        app.add_plugins(ReplayPlugin {
+            mode: ReplayMode::Play(Box::new(replay)),
+        });
+    } else if let Some(path) = args.record {
+        app.add_plugins(ReplayPlugin {
+            mode: ReplayMode::Record(path),
+        });
+    }
+
+    if let Some(session) = net_session {
+        info!(
+            "Playing over the network as player {}",
+            session.local_player
+        );
+        app.insert_resource(session).add_plugins(NetplayPlugin {
+            paced: !args.headless,
+        });
+    }
+
+    if let Some(warning) = high_scores_warning {
+        warn!("{}", warning);
+    }
+
+    app.add_plugins(HighScorePlugin {
+        scores: high_scores,
+        path: high_scores_path,
+    });
+
+    app.insert_resource(RngSeed(seed))
+        .insert_resource(GameRng::from_seed(seed))
+        .add_plugins(GamePlugin {
+            config,
+            waves,
+            mode,
+        })
+        .run()
+}
+
+/// All gameplay resources and systems, independent of whether a window is present.
+struct GamePlugin {
+    config: GameConfig,
+    waves: Waves,
+    /// The mode to start playing straight away, or `None` to open on the title screen.
+    mode: Option<GameMode>,
+}
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        let timers = &self.config.timers;
+
+        let initial_state = match self.mode {
+            Some(_) => GameState::Starting,
+            None => GameState::Title,
+        };
+
+        app.insert_state(initial_state)
+            .init_state::<PauseState>()
+            .add_plugins(MenuPlugin)
+            .insert_resource(self.config.clone())
+            .insert_resource(self.waves.clone())
+            .insert_resource(self.mode.unwrap_or_default())
+            .init_resource::<CurrentWave>()
+            .init_resource::<MarchTempo>()
+            .init_resource::<SpriteSheets>()
+            .init_resource::<Broadphase>()
+            .init_resource::<SpentColliders>()
+            .init_resource::<TurnSnapshots>()
+            .add_event::<CollisionEvent>()
+            .insert_resource(InvaderTimer(Timer::from_seconds(
+                timers.invader_move,
+                TimerMode::Repeating,
+            )))
+            .insert_resource(InvaderShootTimer(Timer::from_seconds(
+                timers.invader_shoot,
+                TimerMode::Repeating,
+            )))
+            .insert_resource(PlayerHitTimer(Timer::from_seconds(
+                timers.player_hit,
+                TimerMode::Once,
+            )))
+            .insert_resource(PlayerHitAnimationTimer(Timer::from_seconds(
+                timers.player_hit_animation,
+                TimerMode::Repeating,
+            )))
+            .insert_resource(RespawningInvadersTimer(Timer::from_seconds(
+                timers.respawning_invaders,
+                TimerMode::Once,
+            )))
+            .insert_resource(MysteryShipTimer(Timer::from_seconds(
+                timers.mystery_ship,
+                TimerMode::Repeating,
+            )))
+            .add_systems(Startup, (log_rng_seed, setup_camera))
+            .add_systems(
+                Update,
                 (
-                    bullets_movement,
-                    bullet_collision_detection,
-                    update_player_score,
-                    update_player_lives,
-                    check_if_invaders_defeated,
+                    players_movement,
+                    (update_march_tempo, invaders_movement).chain(),
+                    (spawn_mystery_ship, mystery_ship_movement).chain(),
+                    spawn_player_bullets,
+                    (assign_shooters, invaders_shooting).chain(),
+                    (
+                        homing_bullets,
+                        bullets_movement,
+                        rebuild_broadphase,
+                        detect_collisions,
+                        clash_bullets,
+                        damage_shields,
+                        kill_invaders,
+                        shoot_down_mystery_ship,
+                        hit_players,
+                        despawn_offscreen_bullets,
+                        check_if_invaders_landed,
+                        update_player_score,
+                        update_high_score,
+                        update_player_lives,
+                        check_if_invaders_defeated,
+                    )
+                        .chain(),
+                )
+                    // Chained so systems drawing from `GameRng` always run in the same order,
+                    // which replays and netplay rely on.
+                    .chain()
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PauseState::Running)),
+            )
+            .add_systems(
+                Update,
+                animate_sprites.run_if(in_state(PauseState::Running)),
+            )
+            .add_systems(
+                OnEnter(GameState::PlayerHit),
+                (despawn_bullets, reset_player_hit_timers),
+            )
+            .add_systems(
+                Update,
+                (player_hit_animation, finish_player_hit)
+                    .chain()
+                    .run_if(in_state(GameState::PlayerHit))
+                    .run_if(in_state(PauseState::Running)),
+            )
+            .add_systems(
+                OnExit(GameState::PlayerHit),
+                (
+                    restore_players,
+                    pass_turn.run_if(resource_equals(GameMode::Alternating)),
                 )
                     .chain(),
             )
-                .run_if(resource_exists_and_equals(GameState::Playing)),
-        )
-        .add_systems(
-            Update,
-            player_hit_animation.run_if(resource_exists_and_equals(GameState::PlayerHit)),
-        )
-        .add_systems(
-            Update,
-            ((game_over, play_again).chain())
-                .run_if(resource_exists_and_equals(GameState::GameOver)),
-        )
-        .add_systems(
-            Update,
-            (
-                (setup_player, setup_player_score, setup_player_lives).chain(),
-                setup_shields,
-                setup_invaders,
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (despawn_bullets, reset_player_hit_timers, game_over),
             )
-                .run_if(resource_exists_and_equals(GameState::Restarting)),
-        )
-        .add_systems(
-            Update,
-            respawn_invaders.run_if(resource_exists_and_equals(GameState::RespawningInvaders)),
-        )
-        .add_systems(
-            Update,
-            pause_game.run_if(resource_exists_and_equals(GameState::Pausing)),
-        )
-        .run();
+            .add_systems(
+                Update,
+                (
+                    player_hit_animation,
+                    play_again
+                        .run_if(not(entering_initials))
+                        .before(enter_initials),
+                )
+                    .run_if(in_state(GameState::GameOver)),
+            )
+            .add_systems(OnEnter(GameState::Title), clear_game)
+            .add_systems(
+                OnEnter(GameState::Starting),
+                (
+                    clear_game,
+                    (
+                        (setup_player, setup_player_score, setup_player_lives).chain(),
+                        setup_high_score,
+                        setup_shields,
+                        setup_invaders,
+                        start_playing,
+                    ),
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(GameState::RespawningInvaders), respawn_invaders)
+            .add_systems(
+                OnEnter(GameState::WaveDelay),
+                reset_respawning_invaders_timer,
+            )
+            .add_systems(
+                Update,
+                wait_for_wave
+                    .run_if(in_state(GameState::WaveDelay))
+                    .run_if(in_state(PauseState::Running)),
+            );
+    }
+}
+
+fn log_simulation_summary(
+    frames: Res<SimulatedFrames>,
+    players: Query<&Player>,
+    invaders: Query<&Invader>,
+) {
+    for player in players.iter() {
+        info!(
+            "Simulated {} frames: player {} score {}, lives {}, {} invaders remaining",
+            frames.0,
+            player.number,
+            player.score,
+            player.lives,
+            invaders.iter().count()
+        );
+    }
+}
+
+fn log_rng_seed(seed: Res<RngSeed>) {
+    info!("Using RNG seed {}", seed.0);
 }
 
 fn setup_camera(mut commands: Commands) {
@@ -146,50 +522,92 @@ fn setup_camera(mut commands: Commands) {
     commands.spawn(camera);
 }
 
-fn setup_player(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_player(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut rng: ResMut<GameRng>,
+    config: Res<GameConfig>,
+    mode: Res<GameMode>,
+) {
     let player_texture: Handle<Image> = asset_server.load("player.png");
 
-    commands.spawn((
-        SpriteBundle {
-            texture: player_texture,
-            transform: Transform::from_translation(Vec3::new(-77.75, -75.0, 0.0)),
-            ..Default::default()
-        },
-        Player {
-            speed: 100.0,
-            lives: 3,
-            score: 0,
-            id: PlayerID {
-                id: rand::thread_rng().gen(),
+    for number in 1..=mode.player_count() {
+        // Players taking turns all start from the first player's spot.
+        let start_x = match *mode {
+            GameMode::Alternating => PLAYER_START_X[0],
+            _ => PLAYER_START_X[number as usize - 1],
+        };
+
+        let mut player = commands.spawn((
+            SpriteBundle {
+                texture: player_texture.clone(),
+                sprite: Sprite {
+                    color: player_color(number),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(Vec3::new(start_x, -75.0, 0.0)),
+                ..Default::default()
             },
-            is_hit: false,
-        },
-    ));
+            Collider::from_sprite("player.png", CollisionLayers::PLAYER, CollisionLayers::NONE),
+            Player {
+                speed: config.player.speed,
+                lives: config.player.lives,
+                score: 0,
+                id: PlayerID { id: rng.0.gen() },
+                is_hit: false,
+                number,
+            },
+            PlayerShootTimer(Timer::from_seconds(
+                config.timers.player_shoot,
+                TimerMode::Once,
+            )),
+        ));
+
+        if *mode == GameMode::Alternating && number > 1 {
+            player
+                .insert((AwaitingTurn {}, Visibility::Hidden))
+                .remove::<Collider>();
+        }
+    }
+}
+
+/// The second player is tinted so the two ships can be told apart.
+fn player_color(number: u32) -> Color {
+    match number {
+        1 => Color::WHITE,
+        _ => Color::srgb(0.4, 0.8, 1.0),
+    }
 }
 
 fn players_movement(
-    mut players: Query<(&mut Transform, &Player)>,
+    mut players: Query<(&mut Transform, &Player), Without<AwaitingTurn>>,
     window: Query<&Window>,
-    input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionInput>,
     time: Res<Time>,
+    config: Res<GameConfig>,
 ) {
-    let mut window_width = WINDOW_WIDTH;
+    let mut window_width = config.window.width;
 
     for window in window.iter() {
         window_width = window.width();
     }
 
     for (mut transform, player) in &mut players {
+        if player.lives == 0 {
+            continue;
+        }
+
+        let actions = actions.player(player.number);
         let movement_amount = player.speed * time.delta_seconds();
 
-        if input.pressed(KeyCode::ArrowLeft) {
+        if actions.pressed(Action::MoveLeft) {
             transform.translation.x -= movement_amount;
 
             if transform.translation.x < -(window_width / 4.0) + 50.0 {
                 transform.translation.x = -(window_width / 4.0) + 50.0;
             }
         }
-        if input.pressed(KeyCode::ArrowRight) {
+        if actions.pressed(Action::MoveRight) {
             transform.translation.x += movement_amount;
 
             if transform.translation.x > (window_width / 4.0) - 50.0 {
@@ -199,255 +617,166 @@ fn players_movement(
     }
 }
 
-fn setup_shields(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let left_shield_textures: Vec<(Vec<Handle<Image>>, Vec2)> = vec![
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield_corner_2.png"),
-                asset_server.load("badly_damaged_shield_corner_2.png"),
-                asset_server.load("slightly_damaged_shield_corner_2.png"),
-                asset_server.load("undamaged_shield_corner_2.png"),
-            ],
-            Vec2::new(-83.75, -40.0),
-        ),
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield.png"),
-                asset_server.load("badly_damaged_shield.png"),
-                asset_server.load("slightly_damaged_shield.png"),
-                asset_server.load("undamaged_shield.png"),
-            ],
-            Vec2::new(-83.75, -46.0),
-        ),
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield.png"),
-                asset_server.load("badly_damaged_shield.png"),
-                asset_server.load("slightly_damaged_shield.png"),
-                asset_server.load("undamaged_shield.png"),
-            ],
-            Vec2::new(-83.75, -52.0),
-        ),
-    ];
-
-    let left_center_shield_textures: Vec<(Vec<Handle<Image>>, Vec2)> = vec![
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield.png"),
-                asset_server.load("badly_damaged_shield.png"),
-                asset_server.load("slightly_damaged_shield.png"),
-                asset_server.load("undamaged_shield.png"),
-            ],
-            Vec2::new(-77.75, -40.0),
-        ),
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield_corner_1.png"),
-                asset_server.load("badly_damaged_shield_corner_1.png"),
-                asset_server.load("slightly_damaged_shield_corner_1.png"),
-                asset_server.load("undamaged_shield_corner_1.png"),
-            ],
-            Vec2::new(-77.75, -46.0),
-        ),
-    ];
-
-    let right_center_shield_textures: Vec<(Vec<Handle<Image>>, Vec2)> = vec![
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield.png"),
-                asset_server.load("badly_damaged_shield.png"),
-                asset_server.load("slightly_damaged_shield.png"),
-                asset_server.load("undamaged_shield.png"),
-            ],
-            Vec2::new(-71.75, -40.0),
-        ),
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield_corner_1.png"),
-                asset_server.load("badly_damaged_shield_corner_1.png"),
-                asset_server.load("slightly_damaged_shield_corner_1.png"),
-                asset_server.load("undamaged_shield_corner_1.png"),
-            ],
-            Vec2::new(-71.75, -46.0),
-        ),
-    ];
-
-    let right_shield_textures: Vec<(Vec<Handle<Image>>, Vec2)> = vec![
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield_corner_2.png"),
-                asset_server.load("badly_damaged_shield_corner_2.png"),
-                asset_server.load("slightly_damaged_shield_corner_2.png"),
-                asset_server.load("undamaged_shield_corner_2.png"),
-            ],
-            Vec2::new(-65.75, -40.0),
-        ),
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield.png"),
-                asset_server.load("badly_damaged_shield.png"),
-                asset_server.load("slightly_damaged_shield.png"),
-                asset_server.load("undamaged_shield.png"),
-            ],
-            Vec2::new(-65.75, -46.0),
-        ),
-        (
-            vec![
-                asset_server.load("heavily_damaged_shield.png"),
-                asset_server.load("badly_damaged_shield.png"),
-                asset_server.load("slightly_damaged_shield.png"),
-                asset_server.load("undamaged_shield.png"),
-            ],
-            Vec2::new(-65.75, -52.0),
-        ),
-    ];
+fn setup_shields(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    spawn_shields(&mut commands, &mut images);
+}
 
+fn spawn_shields(commands: &mut Commands, images: &mut Assets<Image>) {
     for i in 0..SHIELD_NUMS {
-        for (texture_handles, offset) in &left_shield_textures {
-            commands.spawn((
-                ShieldPart {
-                    health: 4,
-                    textures: texture_handles.clone(),
-                    x: offset.x + (i * 50) as f32,
-                    y: offset.y,
-                },
-                SpriteBundle {
-                    texture: texture_handles[3].clone(),
-                    transform: Transform::from_translation(Vec3::new(
-                        offset.x + (i * 50) as f32,
-                        offset.y,
-                        0.0,
-                    )),
-                    ..Default::default()
-                },
-            ));
-        }
+        let shield = Shield::from_shape(SHIELD_SHAPE, Vec2::new(-86.75 + (i * 50) as f32, -37.0));
 
-        for (texture_handles, offset) in &left_center_shield_textures {
-            commands.spawn((
-                ShieldPart {
-                    health: 4,
-                    textures: texture_handles.clone(),
-                    x: offset.x + (i * 50) as f32,
-                    y: offset.y,
-                },
-                SpriteBundle {
-                    texture: texture_handles[3].clone(),
-                    transform: Transform::from_translation(Vec3::new(
-                        offset.x + (i * 50) as f32,
-                        offset.y,
-                        0.0,
-                    )),
-                    ..Default::default()
-                },
-            ));
-        }
+        spawn_shield(commands, images, shield);
+    }
+}
 
-        for (texture_handles, offset) in &right_center_shield_textures {
-            commands.spawn((
-                ShieldPart {
-                    health: 4,
-                    textures: texture_handles.clone(),
-                    x: offset.x + (i * 50) as f32,
-                    y: offset.y,
-                },
-                SpriteBundle {
-                    texture: texture_handles[3].clone(),
-                    transform: Transform {
-                        translation: Vec3::new(offset.x + (i * 50) as f32, offset.y, 0.0),
-                        scale: Vec3::new(-1.0, 1.0, 1.0),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                },
-            ));
-        }
+fn spawn_shield(commands: &mut Commands, images: &mut Assets<Image>, shield: Shield) {
+    let texture = images.add(Image::new(
+        Extent3d {
+            width: shield.width as u32,
+            height: shield.height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        shield.to_rgba(SHIELD_COLOR),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    ));
 
-        for (texture_handles, offset) in &right_shield_textures {
-            commands.spawn((
-                ShieldPart {
-                    health: 4,
-                    textures: texture_handles.clone(),
-                    x: offset.x + (i * 50) as f32,
-                    y: offset.y,
-                },
-                SpriteBundle {
-                    texture: texture_handles[3].clone(),
-                    transform: Transform {
-                        translation: Vec3::new(offset.x + (i * 50) as f32, offset.y, 0.0),
-                        scale: Vec3::new(-1.0, 1.0, 1.0),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                },
-            ));
-        }
-    }
+    commands.spawn((
+        SpriteBundle {
+            texture,
+            transform: Transform::from_translation(shield.center().extend(0.0)),
+            ..Default::default()
+        },
+        Collider::new(
+            Vec2::new(shield.width as f32, shield.height as f32),
+            CollisionLayers::SHIELD,
+            CollisionLayers::NONE,
+        ),
+        shield,
+    ));
 }
 
-fn setup_invaders(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let bottom_invader_texture = asset_server.load("invader-1.png");
-    let middle_invader_texture = asset_server.load("invader-2.png");
-    let top_invader_texture = asset_server.load("invader-3.png");
+fn setup_invaders(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    config: Res<GameConfig>,
+    waves: Res<Waves>,
+    current_wave: Res<CurrentWave>,
+    mut march_tempo: ResMut<MarchTempo>,
+    sprite_sheets: Res<SpriteSheets>,
+) {
+    let Some(wave) = waves.get(current_wave.0) else {
+        return;
+    };
+    let invader_count = spawn_wave(&mut commands, &asset_server, &sprite_sheets, wave);
 
-    for row in 0..INVADER_DECOY_ROWS {
-        for col in 0..INVADER_COLS {
-            commands.spawn((
-                SpriteBundle {
-                    texture: bottom_invader_texture.clone(),
-                    transform: Transform::from_translation(Vec3::new(
-                        -80.0 + col as f32 * 16.0,
-                        6.0 + row as f32 * 16.0,
-                        0.0,
-                    )),
-                    ..Default::default()
-                },
-                Invader {},
-            ));
+    *march_tempo = MarchTempo {
+        interval: config.timers.invader_move,
+        remaining: invader_count,
+        total: invader_count,
+    };
 
-            commands.spawn((
-                SpriteBundle {
-                    texture: middle_invader_texture.clone(),
-                    transform: Transform::from_translation(Vec3::new(
-                        -80.0 + col as f32 * 16.0,
-                        36.0 + row as f32 * 16.0,
-                        0.0,
-                    )),
-                    ..Default::default()
-                },
-                Invader {},
-            ));
-        }
-    }
+    commands.insert_resource(InvaderDirection::Right);
+    insert_wave_resources(&mut commands, &config, wave);
+}
 
-    for row in 0..INVADER_SHOOTER_ROWS {
-        for col in 0..INVADER_COLS {
-            commands.spawn((
-                SpriteBundle {
-                    texture: top_invader_texture.clone(),
-                    transform: Transform::from_translation(Vec3::new(
-                        -80.0 + col as f32 * 16.0,
-                        68.0 + row as f32 * 16.0,
-                        0.0,
-                    )),
-                    ..Default::default()
-                },
-                Invader {},
-                Shooter {},
-            ));
-        }
+/// Spawns `wave`'s formation at its starting height, returning how many invaders it holds.
+fn spawn_wave(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    sprite_sheets: &SpriteSheets,
+    wave: &WaveDefinition,
+) -> usize {
+    let wave_invaders = wave.invaders();
+
+    for wave_invader in &wave_invaders {
+        spawn_invader(
+            commands,
+            asset_server,
+            sprite_sheets,
+            Invader {
+                invader_type: wave_invader.invader_type,
+                column: wave_invader.column,
+            },
+            Vec3::new(
+                wave_invader.offset.x,
+                wave.start_height + wave_invader.offset.y,
+                0.0,
+            ),
+            0,
+        );
     }
 
-    commands.insert_resource(InvaderDirection::Right);
-    commands.insert_resource(InvaderSpeed(INVADER_SPEED));
+    wave_invaders.len()
+}
+
+fn spawn_invader(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    sprite_sheets: &SpriteSheets,
+    invader: Invader,
+    translation: Vec3,
+    frame: usize,
+) {
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load(invader.invader_type.texture()),
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        },
+        TextureAtlas {
+            layout: sprite_sheets.invader.clone(),
+            index: frame,
+        },
+        SpriteAnimation::stepped(0, 1),
+        Collider::from_sprite(
+            invader.invader_type.texture(),
+            CollisionLayers::INVADER,
+            CollisionLayers::SHIELD,
+        ),
+        invader,
+    ));
+}
+
+/// The march speed and firing settings of `wave`, falling back to the config.
+fn insert_wave_resources(commands: &mut Commands, config: &GameConfig, wave: &WaveDefinition) {
+    commands.insert_resource(InvaderSpeed(
+        wave.march_speed.unwrap_or(config.invaders.speed),
+    ));
+    commands.insert_resource(InvaderShootProbability(
+        wave.shoot_probability
+            .unwrap_or(config.invaders.shoot_probability),
+    ));
+    commands.insert_resource(wave.fire_mode.unwrap_or(config.invaders.fire_mode));
+    commands.insert_resource(InvaderBulletProbabilities(
+        wave.bullet_probabilities
+            .clone()
+            .unwrap_or_else(|| config.invaders.bullet_probabilities.clone()),
+    ));
+}
+
+fn update_march_tempo(
+    invaders: Query<(), With<Invader>>,
+    mut march_tempo: ResMut<MarchTempo>,
+    mut timer: ResMut<InvaderTimer>,
+    config: Res<GameConfig>,
+) {
+    march_tempo.remaining = invaders.iter().count();
+    march_tempo.interval = config.march_interval(march_tempo.remaining, march_tempo.total);
+
+    timer
+        .0
+        .set_duration(Duration::from_secs_f32(march_tempo.interval));
 }
 
 fn invaders_movement(
-    mut invaders: Query<&mut Transform, With<Invader>>,
+    mut invaders: Query<(&mut Transform, &mut TextureAtlas, &SpriteAnimation), With<Invader>>,
     mut direction: ResMut<InvaderDirection>,
     speed: Res<InvaderSpeed>,
     time: Res<Time>,
     mut timer: ResMut<InvaderTimer>,
+    config: Res<GameConfig>,
 ) {
     if timer.0.tick(time.delta()).just_finished() {
         let (dx, _) = match *direction {
@@ -457,10 +786,11 @@ fn invaders_movement(
 
         let mut change_direction = false;
 
-        for mut transform in invaders.iter_mut() {
+        for (mut transform, mut atlas, animation) in invaders.iter_mut() {
             transform.translation.x += dx * speed.0 * time.delta_seconds();
+            animation.advance(&mut atlas);
 
-            if transform.translation.x.abs() >= (WINDOW_WIDTH / 2.0) - 11.0 * 18.5 {
+            if transform.translation.x.abs() >= (config.window.width / 2.0) - 11.0 * 18.5 {
                 change_direction = true;
             }
         }
@@ -471,7 +801,7 @@ fn invaders_movement(
                 InvaderDirection::Right => InvaderDirection::Left,
             };
 
-            for mut transform in invaders.iter_mut() {
+            for (mut transform, _, _) in invaders.iter_mut() {
                 transform.translation.x -= dx * speed.0 * time.delta_seconds();
                 transform.translation.y -= 8.0;
             }
@@ -479,77 +809,229 @@ fn invaders_movement(
     }
 }
 
-fn spawn_player_bullets(
+#[allow(clippy::too_many_arguments)]
+fn spawn_mystery_ship(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    player_query: Query<(&Transform, &Player)>,
-    input: Res<ButtonInput<KeyCode>>,
-    mut timer: ResMut<PlayerShootTimer>,
+    invaders: Query<(), With<Invader>>,
+    mystery_ships: Query<(), With<MysteryShip>>,
     time: Res<Time>,
+    mut timer: ResMut<MysteryShipTimer>,
+    mut rng: ResMut<GameRng>,
+    config: Res<GameConfig>,
 ) {
-    timer.0.tick(time.delta());
+    if !timer.0.tick(time.delta()).just_finished()
+        || !mystery_ships.is_empty()
+        || invaders.iter().count() < config.mystery_ship.min_invaders
+    {
+        return;
+    }
 
-    if timer.0.finished() {
-        if input.just_pressed(KeyCode::Space) {
-            if let Some((player_transform, player)) = player_query.iter().next() {
-                let texture = asset_server.load("player-bullet.png");
+    let direction = if rng.0.gen_bool(0.5) { 1.0 } else { -1.0 };
 
-                commands.spawn((
-                    Bullet {
-                        velocity: Vec2::new(0.0, 100.0),
-                    },
-                    PlayerBullet {
-                        player_id: player.id.clone(),
-                    },
-                    SpriteBundle {
-                        texture,
-                        transform: Transform::from_translation(player_transform.translation),
-                        ..Default::default()
-                    },
-                ));
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load("invader-special.png"),
+            transform: Transform::from_translation(Vec3::new(
+                -direction * config.window.width / 4.0,
+                MYSTERY_SHIP_HEIGHT,
+                0.0,
+            )),
+            ..Default::default()
+        },
+        Collider::from_sprite(
+            "invader-special.png",
+            CollisionLayers::MYSTERY_SHIP,
+            CollisionLayers::NONE,
+        ),
+        MysteryShip {
+            velocity: direction * config.mystery_ship.speed,
+        },
+    ));
+}
 
-                timer.0.reset();
-            }
+fn mystery_ship_movement(
+    mut commands: Commands,
+    mut mystery_ships: Query<(Entity, &mut Transform, &MysteryShip)>,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+) {
+    for (mystery_ship_entity, mut transform, mystery_ship) in mystery_ships.iter_mut() {
+        transform.translation.x += mystery_ship.velocity * time.delta_seconds();
+
+        if transform.translation.x.abs() > config.window.width / 4.0 {
+            commands.entity(mystery_ship_entity).despawn();
+        }
+    }
+}
+
+fn animate_sprites(
+    mut commands: Commands,
+    mut animations: Query<(Entity, &mut SpriteAnimation, &mut TextureAtlas)>,
+    time: Res<Time>,
+) {
+    for (entity, mut animation, mut atlas) in animations.iter_mut() {
+        let Some(timer) = animation.timer.as_mut() else {
+            continue;
+        };
+
+        if timer.tick(time.delta()).just_finished() && !animation.advance(&mut atlas) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn spawn_player_bullets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut players: Query<(&Transform, &Player, &mut PlayerShootTimer), Without<AwaitingTurn>>,
+    actions: Res<ActionInput>,
+    time: Res<Time>,
+) {
+    for (player_transform, player, mut timer) in players.iter_mut() {
+        timer.0.tick(time.delta());
+
+        if player.lives == 0
+            || !timer.0.finished()
+            || !actions.player(player.number).just_pressed(Action::Fire)
+        {
+            continue;
         }
+
+        let texture = asset_server.load("player-bullet.png");
+
+        commands.spawn((
+            Bullet {
+                velocity: Vec2::new(0.0, 100.0),
+            },
+            PlayerBullet {
+                player_id: player.id.clone(),
+            },
+            SpriteBundle {
+                texture,
+                transform: Transform::from_translation(player_transform.translation),
+                ..Default::default()
+            },
+            Collider::from_sprite(
+                "player-bullet.png",
+                CollisionLayers::PLAYER_BULLET,
+                CollisionLayers::INVADER_BULLET
+                    | CollisionLayers::SHIELD
+                    | CollisionLayers::INVADER
+                    | CollisionLayers::MYSTERY_SHIP,
+            ),
+            Swept {
+                previous: player_transform.translation.truncate(),
+            },
+        ));
+
+        timer.0.reset();
     }
 }
 
-fn bullets_movement(mut bullets: Query<(&mut Transform, &Bullet)>, time: Res<Time>) {
-    for (mut transform, bullet) in bullets.iter_mut() {
+fn bullets_movement(mut bullets: Query<(&mut Transform, &Bullet, &mut Swept)>, time: Res<Time>) {
+    for (mut transform, bullet, mut swept) in bullets.iter_mut() {
+        swept.previous = transform.translation.truncate();
         transform.translation +=
             Vec3::new(bullet.velocity.x, bullet.velocity.y, 0.0) * time.delta_seconds();
     }
 }
 
+/// Hands [`Shooter`] to the lowest invader left in each column.
+fn assign_shooters(
+    mut commands: Commands,
+    invaders: Query<(Entity, &Transform, &Invader, Has<Shooter>)>,
+) {
+    let mut front_of_column: HashMap<usize, (Entity, f32)> = HashMap::new();
+
+    for (entity, transform, invader, _) in invaders.iter() {
+        let y = transform.translation.y;
+
+        front_of_column
+            .entry(invader.column)
+            .and_modify(|front| {
+                if y < front.1 {
+                    *front = (entity, y);
+                }
+            })
+            .or_insert((entity, y));
+    }
+
+    for (entity, _, invader, is_shooter) in invaders.iter() {
+        let is_front = front_of_column[&invader.column].0 == entity;
+
+        if is_front && !is_shooter {
+            commands.entity(entity).insert(Shooter {});
+        } else if !is_front && is_shooter {
+            commands.entity(entity).remove::<Shooter>();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn invaders_shooting(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    shooter_invaders: Query<&Transform, With<Shooter>>,
+    shooter_invaders: Query<(Entity, &Transform, &Invader), With<Shooter>>,
+    players: Query<(&Transform, &Player), Without<AwaitingTurn>>,
     time: Res<Time>,
     mut timer: ResMut<InvaderShootTimer>,
+    mut rng: ResMut<GameRng>,
+    shoot_probability: Res<InvaderShootProbability>,
+    fire_mode: Res<InvaderFireMode>,
+    bullet_probabilities: Res<InvaderBulletProbabilities>,
+    config: Res<GameConfig>,
 ) {
     timer.0.tick(time.delta());
 
     if timer.0.finished() {
-        let mut rng = rand::thread_rng();
+        let aimed_shooter = match *fire_mode {
+            InvaderFireMode::Random => None,
+            InvaderFireMode::Aimed => shooter_invaders
+                .iter()
+                .map(|(entity, transform, _)| {
+                    let distance = players
+                        .iter()
+                        .filter(|(_, player)| player.lives > 0)
+                        .map(|(player, _)| (player.translation.x - transform.translation.x).abs())
+                        .fold(f32::INFINITY, f32::min);
+
+                    (entity, distance)
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(entity, _)| entity),
+        };
 
-        for transform in shooter_invaders.iter() {
-            let mut roll = rng.gen_range(0.0..1.0);
+        for (entity, transform, invader) in shooter_invaders.iter() {
+            let mut roll = rng.0.gen_range(0.0..1.0);
 
-            if roll > INVADER_SHOOT_PROBABILITY {
+            if aimed_shooter != Some(entity)
+                && roll
+                    > shoot_probability.0
+                        * config.invaders.fire_weights.weight(invader.invader_type)
+            {
                 continue;
             }
 
-            roll = rng.gen_range(0.0..1.0);
+            roll = rng.0.gen_range(0.0..1.0);
             let mut cumulative_probability = 0.0;
 
-            for prob in INVADER_BULLET_PROBABILITIES {
+            for prob in &bullet_probabilities.0 {
                 cumulative_probability += prob.probability;
 
                 if roll < cumulative_probability {
-                    let texture = match prob.bullet_type {
-                        InvaderBulletType::Bullet => asset_server.load("invader-bullet.png"),
-                        InvaderBulletType::Bolt => asset_server.load("invader-bolt.png"),
+                    let (texture, color) = match prob.bullet_type {
+                        InvaderBulletType::Bullet => ("invader-bullet.png", Color::WHITE),
+                        InvaderBulletType::Bolt => ("invader-bolt.png", Color::WHITE),
+                        InvaderBulletType::Homing => {
+                            ("invader-bullet.png", Color::srgb(1.0, 0.4, 0.4))
+                        }
+                    };
+
+                    // Bolts pass straight through shields.
+                    let mask = match prob.bullet_type {
+                        InvaderBulletType::Bolt => CollisionLayers::PLAYER,
+                        _ => CollisionLayers::SHIELD | CollisionLayers::PLAYER,
                     };
 
                     commands.spawn((
@@ -557,16 +1039,21 @@ fn invaders_shooting(
                             velocity: Vec2::new(0.0, -100.0),
                         },
                         InvaderBullet {
-                            bullet_type: match prob.bullet_type {
-                                InvaderBulletType::Bullet => InvaderBulletType::Bullet,
-                                InvaderBulletType::Bolt => InvaderBulletType::Bolt,
-                            },
+                            bullet_type: prob.bullet_type,
                         },
                         SpriteBundle {
-                            texture,
+                            texture: asset_server.load(texture),
+                            sprite: Sprite {
+                                color,
+                                ..Default::default()
+                            },
                             transform: Transform::from_translation(transform.translation),
                             ..Default::default()
                         },
+                        Collider::from_sprite(texture, CollisionLayers::INVADER_BULLET, mask),
+                        Swept {
+                            previous: transform.translation.truncate(),
+                        },
                     ));
                     break;
                 }
@@ -575,244 +1062,570 @@ fn invaders_shooting(
     }
 }
 
-fn bullet_collision_detection(
+/// Steers homing shots sideways towards the nearest player.
+fn homing_bullets(
+    mut bullets: Query<(&mut Bullet, &Transform, &InvaderBullet)>,
+    players: Query<(&Transform, &Player), Without<AwaitingTurn>>,
+    config: Res<GameConfig>,
+) {
+    for (mut bullet, transform, invader_bullet) in bullets.iter_mut() {
+        if !matches!(invader_bullet.bullet_type, InvaderBulletType::Homing) {
+            continue;
+        }
+
+        let nearest = players
+            .iter()
+            .filter(|(_, player)| player.lives > 0)
+            .map(|(player, _)| player.translation.x - transform.translation.x)
+            .min_by(|a, b| a.abs().total_cmp(&b.abs()));
+
+        if let Some(dx) = nearest {
+            let drift = config.invaders.homing_drift;
+            bullet.velocity.x = (dx * 2.0).clamp(-drift, drift);
+        }
+    }
+}
+
+fn award_points(players: &mut Query<&mut Player>, player_id: &PlayerID, points: u32) {
+    for mut player in players.iter_mut() {
+        if player.id.id == player_id.id {
+            player.score += points;
+            break;
+        }
+    }
+}
+
+fn clash_bullets(
     mut commands: Commands,
-    mut shields: Query<(Entity, &Transform, &mut ShieldPart)>,
-    mut player_bullets: Query<(Entity, &Transform, &PlayerBullet)>,
-    mut invader_bullets: Query<(Entity, &Transform, &InvaderBullet)>,
-    mut players: Query<(Entity, &Transform, &mut Player)>,
-    mut invaders: Query<(Entity, &Transform, &Invader)>,
-    mut game_state: ResMut<GameState>,
+    mut collisions: EventReader<CollisionEvent>,
+    player_bullets: Query<&PlayerBullet>,
+    invader_bullets: Query<&InvaderBullet>,
+    mut players: Query<&mut Player>,
+    mut spent: ResMut<SpentColliders>,
+    config: Res<GameConfig>,
 ) {
-    for (player_bullet_entity, player_bullet_transform, player_bullet) in player_bullets.iter_mut()
+    for collision in collisions
+        .read()
+        .filter(|collision| collision.layer == CollisionLayers::INVADER_BULLET)
     {
-        let mut has_despawned = false;
+        if spent.involves(collision) {
+            continue;
+        }
 
-        for (shield_entity, shield_transform, mut shield) in shields.iter_mut() {
-            let dist = player_bullet_transform.translation - Vec3::new(shield.x, shield.y, 0.0);
+        let (Ok(player_bullet), Ok(invader_bullet)) = (
+            player_bullets.get(collision.entity),
+            invader_bullets.get(collision.other),
+        ) else {
+            continue;
+        };
 
-            if dist.x.abs() < 4.0 && dist.y.abs() < 4.0 {
-                shield.health -= 1;
+        let rule = config.bullet_clashes.rule(invader_bullet.bullet_type);
 
-                if shield.health <= 0 {
-                    commands.entity(shield_entity).despawn();
-                } else {
-                    let new_texture = shield.current_texture();
+        match rule.outcome {
+            BulletClashOutcome::BothDestroyed => {
+                award_points(&mut players, &player_bullet.player_id, rule.points);
+                commands.entity(collision.other).despawn();
+                spent.spend(collision.other);
+            }
+            BulletClashOutcome::PlayerShotAbsorbed => {}
+            BulletClashOutcome::PassThrough => continue,
+        }
 
-                    commands.entity(shield_entity).insert((
-                        ShieldPart {
-                            health: shield.health,
-                            textures: shield.textures.clone(),
-                            x: shield.x,
-                            y: shield.y,
-                        },
-                        SpriteBundle {
-                            texture: new_texture,
-                            transform: shield_transform.clone(),
-                            ..Default::default()
-                        },
-                    ));
-                }
+        commands.entity(collision.entity).despawn();
+        spent.spend(collision.entity);
+    }
+}
 
-                commands.entity(player_bullet_entity).despawn();
-                has_despawned = true;
-                break;
-            }
+/// Shots blow a hole in the shield where they land and are used up; invaders scrape away
+/// whatever part of a shield they overlap.
+fn damage_shields(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    mut shields: Query<(&mut Shield, &Handle<Image>)>,
+    mut images: ResMut<Assets<Image>>,
+    colliders: Query<(&Transform, &Collider, Has<PlayerBullet>, Has<InvaderBullet>)>,
+    mut spent: ResMut<SpentColliders>,
+) {
+    for collision in collisions
+        .read()
+        .filter(|collision| collision.layer == CollisionLayers::SHIELD)
+    {
+        if spent.involves(collision) {
+            continue;
         }
 
-        for (invader_entity, invader_transform, _) in invaders.iter_mut() {
-            let invader_transform =
-                player_bullet_transform.translation - invader_transform.translation;
+        let (
+            Ok((mut shield, texture)),
+            Ok((transform, collider, is_player_bullet, is_invader_bullet)),
+        ) = (
+            shields.get_mut(collision.other),
+            colliders.get(collision.entity),
+        )
+        else {
+            continue;
+        };
 
-            if invader_transform.x.abs() < 4.0 && invader_transform.y.abs() < 4.0 {
-                for (_, _, mut player) in players.iter_mut() {
-                    if player_bullet.player_id.id == player.id.id {
-                        player.score += 1;
-                        break;
-                    }
-                }
+        let point = collision.point;
+
+        if is_player_bullet {
+            shield.carve(point, PLAYER_BULLET_EXPLOSION);
+            commands.entity(collision.entity).despawn();
+            spent.spend(collision.entity);
+        } else if is_invader_bullet {
+            shield.carve(point, INVADER_BULLET_EXPLOSION);
+            commands.entity(collision.entity).despawn();
+            spent.spend(collision.entity);
+        } else {
+            shield.clear(collider.aabb(transform));
+        }
 
-                commands.entity(player_bullet_entity).despawn();
-                commands.entity(invader_entity).despawn();
-                has_despawned = true;
-                break;
-            }
+        if let Some(image) = images.get_mut(texture) {
+            image.data = shield.to_rgba(SHIELD_COLOR);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn kill_invaders(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    invaders: Query<(&Transform, &Invader)>,
+    player_bullets: Query<&PlayerBullet>,
+    mut players: Query<&mut Player>,
+    asset_server: Res<AssetServer>,
+    sprite_sheets: Res<SpriteSheets>,
+    mut spent: ResMut<SpentColliders>,
+    config: Res<GameConfig>,
+) {
+    for collision in collisions
+        .read()
+        .filter(|collision| collision.layer == CollisionLayers::INVADER)
+    {
+        if spent.contains(collision.entity) {
+            continue;
+        }
+
+        let (Ok(player_bullet), Ok((invader_transform, invader))) = (
+            player_bullets.get(collision.entity),
+            invaders.get(collision.other),
+        ) else {
+            continue;
+        };
+
+        commands.entity(collision.entity).despawn();
+        spent.spend(collision.entity);
+
+        if spent.contains(collision.other) {
+            continue;
+        }
+
+        spent.spend(collision.other);
+        award_points(
+            &mut players,
+            &player_bullet.player_id,
+            config.scoring.invader_points(invader.invader_type),
+        );
+
+        commands.entity(collision.other).despawn();
+        commands.spawn((
+            SpriteBundle {
+                texture: asset_server.load("invader-explosion.png"),
+                transform: *invader_transform,
+                ..Default::default()
+            },
+            TextureAtlas {
+                layout: sprite_sheets.explosion.clone(),
+                index: 0,
+            },
+            SpriteAnimation::timed(0, 0, config.timers.invader_explosion, true),
+        ));
+    }
+}
+
+fn shoot_down_mystery_ship(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    player_bullets: Query<&PlayerBullet>,
+    mut players: Query<&mut Player>,
+    mut rng: ResMut<GameRng>,
+    mut spent: ResMut<SpentColliders>,
+    config: Res<GameConfig>,
+) {
+    for collision in collisions
+        .read()
+        .filter(|collision| collision.layer == CollisionLayers::MYSTERY_SHIP)
+    {
+        if spent.contains(collision.entity) {
+            continue;
+        }
+
+        let Ok(player_bullet) = player_bullets.get(collision.entity) else {
+            continue;
+        };
+
+        commands.entity(collision.entity).despawn();
+        spent.spend(collision.entity);
+
+        if spent.contains(collision.other) {
+            continue;
+        }
+
+        spent.spend(collision.other);
+
+        let points =
+            config.scoring.mystery_ship[rng.0.gen_range(0..config.scoring.mystery_ship.len())];
+        award_points(&mut players, &player_bullet.player_id, points);
+
+        commands.entity(collision.other).despawn();
+    }
+}
+
+fn hit_players(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    invader_bullets: Query<(), With<InvaderBullet>>,
+    mut players: Query<&mut Player>,
+    mut spent: ResMut<SpentColliders>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let mut hit: Vec<Entity> = Vec::new();
+
+    for collision in collisions
+        .read()
+        .filter(|collision| collision.layer == CollisionLayers::PLAYER)
+    {
+        if spent.contains(collision.entity) || !invader_bullets.contains(collision.entity) {
+            continue;
+        }
+
+        let Ok(mut player) = players.get_mut(collision.other) else {
+            continue;
+        };
+
+        commands.entity(collision.entity).despawn();
+        spent.spend(collision.entity);
+
+        if hit.contains(&collision.other) {
+            continue;
+        }
+
+        hit.push(collision.other);
+        player.lives -= 1;
+        player.is_hit = true;
+    }
+
+    if hit.is_empty() {
+        return;
+    }
+
+    if players.iter().all(|player| player.lives == 0) {
+        next_state.set(GameState::GameOver);
+    } else {
+        next_state.set(GameState::PlayerHit);
+    }
+}
+
+fn despawn_offscreen_bullets(
+    mut commands: Commands,
+    player_bullets: Query<(Entity, &Transform), With<PlayerBullet>>,
+    invader_bullets: Query<(Entity, &Transform), With<InvaderBullet>>,
+    spent: Res<SpentColliders>,
+    config: Res<GameConfig>,
+) {
+    for (player_bullet_entity, transform) in player_bullets.iter() {
+        if transform.translation.y > config.window.height / 4.0 - 25.0
+            && !spent.contains(player_bullet_entity)
+        {
+            commands.entity(player_bullet_entity).despawn();
+        }
+    }
+
+    for (invader_bullet_entity, transform) in invader_bullets.iter() {
+        if transform.translation.y < -config.window.height / 4.0 + 25.0
+            && !spent.contains(invader_bullet_entity)
+        {
+            commands.entity(invader_bullet_entity).despawn();
         }
+    }
+}
+
+/// Invaders reaching the player's row take every life the players in play have left.
+fn check_if_invaders_landed(
+    invaders: Query<(&Transform, &Collider), With<Invader>>,
+    mut players: Query<(&Transform, Option<&Collider>, &mut Player)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(lowest) = invaders
+        .iter()
+        .map(|(transform, collider)| collider.aabb(transform).min.y)
+        .min_by(f32::total_cmp)
+    else {
+        return;
+    };
+
+    let landed = players.iter().any(|(transform, collider, _)| {
+        collider.is_some_and(|collider| lowest <= collider.aabb(transform).max.y)
+    });
+
+    if !landed {
+        return;
+    }
+
+    // Only the players in play are overrun; one waiting for their turn still gets it.
+    for (_, _, mut player) in players
+        .iter_mut()
+        .filter(|(_, collider, _)| collider.is_some())
+    {
+        player.lives = 0;
+        player.is_hit = true;
+    }
+
+    if players.iter().all(|(_, _, player)| player.lives == 0) {
+        next_state.set(GameState::GameOver);
+    } else {
+        next_state.set(GameState::PlayerHit);
+    }
+}
+
+fn despawn_bullets(mut commands: Commands, bullets: Query<Entity, With<Bullet>>) {
+    for bullet_entity in bullets.iter() {
+        commands.entity(bullet_entity).despawn();
+    }
+}
+
+fn reset_player_hit_timers(
+    mut player_hit_timer: ResMut<PlayerHitTimer>,
+    mut player_hit_animation_timer: ResMut<PlayerHitAnimationTimer>,
+) {
+    player_hit_timer.0.reset();
+    player_hit_animation_timer.0.reset();
+}
+
+fn player_hit_animation(
+    mut players: Query<(&mut Handle<Image>, &Player)>,
+    asset_server: Res<AssetServer>,
+    mut player_hit_timer: ResMut<PlayerHitTimer>,
+    mut player_hit_animation_timer: ResMut<PlayerHitAnimationTimer>,
+    time: Res<Time>,
+) {
+    if player_hit_timer.0.finished() {
+        return;
+    }
+
+    player_hit_timer.0.tick(time.delta());
+    player_hit_animation_timer.0.tick(time.delta());
+
+    let player_hit_1_texture: Handle<Image> = asset_server.load("player-hit-1.png");
+    let player_hit_2_texture: Handle<Image> = asset_server.load("player-hit-2.png");
 
-        if !has_despawned {
-            if player_bullet_transform.translation.y > WINDOW_HEIGHT / 4.0 - 25.0 {
-                commands.entity(player_bullet_entity).despawn();
+    if player_hit_animation_timer.0.finished() {
+        for (mut player_texture, _) in players.iter_mut().filter(|(_, player)| player.is_hit) {
+            if time.elapsed().as_secs_f32() % 0.2 < 0.1 {
+                *player_texture = player_hit_2_texture.clone();
+            } else {
+                *player_texture = player_hit_1_texture.clone();
             }
         }
+        player_hit_animation_timer.0.reset();
     }
+}
 
-    for (invader_bullet_entity, invader_bullet_transform, invader_bullet) in
-        invader_bullets.iter_mut()
-    {
-        let mut has_despawned = false;
-
-        match invader_bullet.bullet_type {
-            InvaderBulletType::Bullet => {
-                for (shield_entity, shield_transform, mut shield) in shields.iter_mut() {
-                    let dist =
-                        invader_bullet_transform.translation - Vec3::new(shield.x, shield.y, 0.0);
-
-                    if dist.x.abs() < 8.0 && dist.y.abs() < 8.0 {
-                        shield.health -= 1;
-
-                        if shield.health <= 0 {
-                            commands.entity(shield_entity).despawn();
-                        } else {
-                            let new_texture = shield.current_texture();
-
-                            commands.entity(shield_entity).insert((
-                                ShieldPart {
-                                    health: shield.health,
-                                    textures: shield.textures.clone(),
-                                    x: shield.x,
-                                    y: shield.y,
-                                },
-                                SpriteBundle {
-                                    texture: new_texture,
-                                    transform: shield_transform.clone(),
-                                    ..Default::default()
-                                },
-                            ));
-                        }
-
-                        commands.entity(invader_bullet_entity).despawn();
-                        has_despawned = true;
-                        break;
-                    }
-                }
+fn finish_player_hit(
+    player_hit_timer: Res<PlayerHitTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if player_hit_timer.0.finished() {
+        next_state.set(GameState::Playing);
+    }
+}
 
-                for (_, player_transform, mut player) in players.iter_mut() {
-                    let dist = invader_bullet_transform.translation - player_transform.translation;
+/// Puts hit players back to normal, and takes any player out of a co-op game once they have
+/// no lives left.
+fn restore_players(
+    mut commands: Commands,
+    mut players: Query<(Entity, &mut Handle<Image>, &mut Visibility, &mut Player)>,
+    asset_server: Res<AssetServer>,
+) {
+    let player_texture: Handle<Image> = asset_server.load("player.png");
 
-                    if dist.x.abs() < 8.0 && dist.y.abs() < 8.0 {
-                        player.lives -= 1;
-                        player.is_hit = true;
-                        if player.lives == 0 {
-                            *game_state = GameState::GameOver;
-                        } else {
-                            *game_state = GameState::PlayerHit;
-                        }
-                        commands.entity(invader_bullet_entity).despawn();
-                        has_despawned = true;
-                        break;
-                    }
-                }
-            }
-            InvaderBulletType::Bolt => {
-                for (_, player_transform, mut player) in players.iter_mut() {
-                    let dist = invader_bullet_transform.translation - player_transform.translation;
-
-                    if dist.x.abs() < 8.0 && dist.y.abs() < 8.0 {
-                        player.lives -= 1;
-                        player.is_hit = true;
-                        if player.lives == 0 {
-                            *game_state = GameState::GameOver;
-                        } else {
-                            *game_state = GameState::PlayerHit;
-                        }
-                        commands.entity(invader_bullet_entity).despawn();
-                        has_despawned = true;
-                        break;
-                    }
-                }
-            }
-        }
+    for (entity, mut texture, mut visibility, mut player) in players.iter_mut() {
+        *texture = player_texture.clone();
+        player.is_hit = false;
 
-        if !has_despawned {
-            if invader_bullet_transform.translation.y < -WINDOW_HEIGHT / 4.0 + 25.0 {
-                commands.entity(invader_bullet_entity).despawn();
-            }
+        if player.lives == 0 {
+            *visibility = Visibility::Hidden;
+            commands.entity(entity).remove::<Collider>();
         }
     }
 }
 
-fn player_hit_animation(
+/// Hands the game to the next player with lives left in an alternating game, putting away the
+/// invaders and shields of the player whose turn ended and bringing back their own.
+#[allow(clippy::too_many_arguments)]
+fn pass_turn(
     mut commands: Commands,
-    mut players: Query<(Entity, &Transform, &mut Player)>,
-    bullets: Query<(Entity, &Transform, &Bullet)>,
-    mut game_state: ResMut<GameState>,
+    mut players: Query<(Entity, &mut Visibility, &Player, Has<AwaitingTurn>)>,
+    invaders: Query<(Entity, &Transform, &TextureAtlas, &Invader)>,
+    mystery_ships: Query<Entity, With<MysteryShip>>,
+    shields: Query<(Entity, &Shield)>,
+    mut snapshots: ResMut<TurnSnapshots>,
+    mut current_wave: ResMut<CurrentWave>,
+    mut march_tempo: ResMut<MarchTempo>,
+    direction: Res<InvaderDirection>,
     asset_server: Res<AssetServer>,
-    mut player_hit_timer: ResMut<PlayerHitTimer>,
-    mut player_hit_animation_timer: ResMut<PlayerHitAnimationTimer>,
-    time: Res<Time>,
+    mut images: ResMut<Assets<Image>>,
+    sprite_sheets: Res<SpriteSheets>,
+    config: Res<GameConfig>,
+    waves: Res<Waves>,
 ) {
-    player_hit_timer.0.tick(time.delta());
-    player_hit_animation_timer.0.tick(time.delta());
+    let mut turn_order: Vec<(u32, u32, bool)> = players
+        .iter()
+        .map(|(_, _, player, waiting)| (player.number, player.lives, waiting))
+        .collect();
+    turn_order.sort_by_key(|(number, _, _)| *number);
 
-    if player_hit_timer.0.finished() {
-        let player_texture: Handle<Image> = asset_server.load("player.png");
+    let Some(current) = turn_order.iter().position(|(_, _, waiting)| !waiting) else {
+        return;
+    };
 
-        *game_state = GameState::Playing;
+    let Some(next) = (1..turn_order.len())
+        .map(|step| (current + step) % turn_order.len())
+        .find(|index| turn_order[*index].1 > 0)
+    else {
+        return;
+    };
 
-        for (player_entity, player_transform, _) in players.iter_mut() {
-            commands.entity(player_entity).insert(SpriteBundle {
-                texture: player_texture.clone(),
-                transform: player_transform.clone(),
-                ..Default::default()
-            });
+    let (current, next) = (turn_order[current].0, turn_order[next].0);
+
+    snapshots.0.insert(
+        current,
+        PlayerSnapshot {
+            wave: current_wave.0,
+            invaders: invaders
+                .iter()
+                .map(|(_, transform, atlas, invader)| InvaderSnapshot {
+                    invader: *invader,
+                    translation: transform.translation,
+                    frame: atlas.index,
+                })
+                .collect(),
+            direction: *direction,
+            shields: shields.iter().map(|(_, shield)| shield.clone()).collect(),
+        },
+    );
+
+    for entity in invaders
+        .iter()
+        .map(|(entity, _, _, _)| entity)
+        .chain(mystery_ships.iter())
+        .chain(shields.iter().map(|(entity, _)| entity))
+    {
+        commands.entity(entity).despawn();
+    }
+
+    for (entity, mut visibility, player, _) in players.iter_mut() {
+        if player.number == current {
+            *visibility = Visibility::Hidden;
+            commands
+                .entity(entity)
+                .insert(AwaitingTurn {})
+                .remove::<Collider>();
+        } else if player.number == next {
+            *visibility = Visibility::Inherited;
+            commands
+                .entity(entity)
+                .remove::<AwaitingTurn>()
+                .insert(Collider::from_sprite(
+                    "player.png",
+                    CollisionLayers::PLAYER,
+                    CollisionLayers::NONE,
+                ));
         }
+    }
+
+    // A player's first turn starts from the first wave with fresh shields.
+    let Some(snapshot) = snapshots.0.remove(&next) else {
+        current_wave.0 = 0;
+        let Some(wave) = waves.get(0) else {
+            return;
+        };
+        let invader_count = spawn_wave(&mut commands, &asset_server, &sprite_sheets, wave);
 
-        player_hit_timer.0.reset();
+        *march_tempo = MarchTempo {
+            interval: config.timers.invader_move,
+            remaining: invader_count,
+            total: invader_count,
+        };
+
+        spawn_shields(&mut commands, &mut images);
+        commands.insert_resource(InvaderDirection::Right);
+        insert_wave_resources(&mut commands, &config, wave);
         return;
-    }
+    };
 
-    let player_hit_1_texture: Handle<Image> = asset_server.load("player-hit-1.png");
-    let player_hit_2_texture: Handle<Image> = asset_server.load("player-hit-2.png");
+    current_wave.0 = snapshot.wave;
+    let Some(wave) = waves.get(snapshot.wave) else {
+        return;
+    };
 
-    if player_hit_animation_timer.0.finished() {
-        for (player_entity, player_transform, _) in players.iter_mut() {
-            if time.elapsed().as_secs_f32() % 0.2 < 0.1 {
-                commands.entity(player_entity).insert(SpriteBundle {
-                    texture: player_hit_2_texture.clone(),
-                    transform: player_transform.clone(),
-                    ..Default::default()
-                });
-            } else {
-                commands.entity(player_entity).insert(SpriteBundle {
-                    texture: player_hit_1_texture.clone(),
-                    transform: player_transform.clone(),
-                    ..Default::default()
-                });
-            }
-        }
-        player_hit_animation_timer.0.reset();
+    for saved in &snapshot.invaders {
+        spawn_invader(
+            &mut commands,
+            &asset_server,
+            &sprite_sheets,
+            saved.invader,
+            saved.translation,
+            saved.frame,
+        );
     }
 
-    for (bullet_entity, _, _) in bullets.iter() {
-        commands.entity(bullet_entity).despawn();
+    *march_tempo = MarchTempo {
+        interval: config.timers.invader_move,
+        remaining: snapshot.invaders.len(),
+        total: wave.invaders().len(),
+    };
+
+    for shield in snapshot.shields {
+        spawn_shield(&mut commands, &mut images, shield);
     }
+
+    commands.insert_resource(snapshot.direction);
+    insert_wave_resources(&mut commands, &config, wave);
 }
 
-fn setup_player_score(mut commands: Commands, player: Query<&Player>) {
-    commands
-        .spawn(TextBundle {
-            text: Text {
-                sections: vec![TextSection {
-                    value: format!("Score: {}", player.iter().next().unwrap().score),
-                    style: TextStyle {
-                        font: Default::default(),
-                        font_size: 30.0,
-                        color: Color::WHITE,
-                    },
-                }],
-                ..Default::default()
-            },
-            style: Style {
-                position_type: PositionType::Absolute,
-                top: Val::Px(10.0),
-                left: Val::Px(10.0),
+fn setup_player_score(mut commands: Commands, players: Query<&Player>, mode: Res<GameMode>) {
+    for player in players.iter() {
+        let label = match *mode {
+            GameMode::Single => "Score".to_string(),
+            GameMode::CoOp | GameMode::Alternating => format!("P{} Score", player.number),
+        };
+
+        commands
+            .spawn(TextBundle {
+                text: Text {
+                    sections: vec![TextSection {
+                        value: format!("{}: {}", label, player.score),
+                        style: TextStyle {
+                            font: Default::default(),
+                            font_size: 30.0,
+                            color: player_color(player.number),
+                        },
+                    }],
+                    ..Default::default()
+                },
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0 + (player.number - 1) as f32 * 30.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
                 ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(PlayerScore {
-            player_id: player.iter().next().unwrap().id.clone(),
-        });
+            })
+            .insert(PlayerScore {
+                player_id: player.id.clone(),
+                label,
+            });
+    }
 }
 
 fn update_player_score(mut texts: Query<(&mut Text, &PlayerScore)>, players: Query<&Player>) {
@@ -821,132 +1634,140 @@ fn update_player_score(mut texts: Query<(&mut Text, &PlayerScore)>, players: Que
             if player.id.id != player_score.player_id.id {
                 continue;
             }
-            text.sections[0].value = format!("Score: {}", player.score);
+            text.sections[0].value = format!("{}: {}", player_score.label, player.score);
         }
     }
 }
 
+fn setup_high_score(mut commands: Commands, high_scores: Res<HighScores>, mode: Res<GameMode>) {
+    commands.spawn((
+        TextBundle::from_section(
+            format!("HI-SCORE\n{}", high_scores.best(*mode)),
+            TextStyle {
+                font: Default::default(),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            width: Val::Percent(100.0),
+            ..Default::default()
+        }),
+        HighScoreText {},
+    ));
+}
+
+/// Shows the best score on the mode's table, or a player's score once it beats that.
+fn update_high_score(
+    mut texts: Query<&mut Text, With<HighScoreText>>,
+    players: Query<&Player>,
+    high_scores: Res<HighScores>,
+    mode: Res<GameMode>,
+) {
+    let best = players
+        .iter()
+        .map(|player| player.score)
+        .fold(high_scores.best(*mode), u32::max);
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("HI-SCORE\n{best}");
+    }
+}
+
 fn setup_player_lives(
     mut commands: Commands,
-    player: Query<&Player>,
+    players: Query<&Player>,
     asset_server: Res<AssetServer>,
-    mut game_state: ResMut<GameState>,
+    config: Res<GameConfig>,
+    mode: Res<GameMode>,
 ) {
-    let player = player.iter().next().unwrap();
     let player_texture = asset_server.load("player.png");
 
-    commands.spawn(TextBundle {
-        text: Text {
-            sections: vec![TextSection {
-                value: "Lives".to_string(),
-                style: TextStyle {
-                    font: Default::default(),
-                    font_size: 30.0,
-                    color: Color::WHITE,
-                },
-            }],
-            ..Default::default()
-        },
-        style: Style {
-            position_type: PositionType::Absolute,
-            top: Val::Px(10.0),
-            right: Val::Px(200.0),
-            ..Default::default()
-        },
-        ..Default::default()
-    });
+    for player in players.iter() {
+        let row = (player.number - 1) as f32;
+        let label = match *mode {
+            GameMode::Single => "Lives".to_string(),
+            GameMode::CoOp | GameMode::Alternating => format!("P{} Lives", player.number),
+        };
 
-    for i in 0..player.lives {
         commands.spawn((
-            SpriteBundle {
-                texture: player_texture.clone(),
-                transform: Transform::from_translation(Vec3::new(
-                    WINDOW_WIDTH / 10.0 + i as f32 * 20.0,
-                    WINDOW_HEIGHT / 8.0 + 24.0,
-                    0.0,
-                )),
+            TextBundle {
+                text: Text {
+                    sections: vec![TextSection {
+                        value: label,
+                        style: TextStyle {
+                            font: Default::default(),
+                            font_size: 30.0,
+                            color: player_color(player.number),
+                        },
+                    }],
+                    ..Default::default()
+                },
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0 + row * 30.0),
+                    right: Val::Px(200.0),
+                    ..Default::default()
+                },
                 ..Default::default()
             },
-            PlayerLife {
-                player_id: player.id.clone(),
-            },
+            PlayerLivesText {},
         ));
-    }
 
-    match *game_state {
-        GameState::Restarting => *game_state = GameState::Playing,
-        _ => {}
+        for i in 0..player.lives {
+            commands.spawn((
+                SpriteBundle {
+                    texture: player_texture.clone(),
+                    sprite: Sprite {
+                        color: player_color(player.number),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(Vec3::new(
+                        config.window.width / 10.0 + i as f32 * 20.0,
+                        config.window.height / 8.0 + 24.0 - row * 12.0,
+                        0.0,
+                    )),
+                    ..Default::default()
+                },
+                PlayerLife {
+                    player_id: player.id.clone(),
+                    index: i,
+                },
+            ));
+        }
     }
 }
 
 fn update_player_lives(
     mut commands: Commands,
-    player: Query<&Player>,
-    mut player_lives: Query<(Entity, &PlayerLife)>,
+    players: Query<&Player>,
+    player_lives: Query<(Entity, &PlayerLife)>,
 ) {
-    let player = player.iter().next().unwrap();
-
-    for (i, (player_life_entity, player_life)) in player_lives.iter_mut().enumerate() {
-        if i >= player.lives as usize && player_life.player_id.id == player.id.id {
-            commands.entity(player_life_entity).despawn();
+    for player in players.iter() {
+        for (player_life_entity, player_life) in player_lives.iter() {
+            if player_life.index >= player.lives && player_life.player_id.id == player.id.id {
+                commands.entity(player_life_entity).despawn();
+            }
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn game_over(
     mut commands: Commands,
-    bullets: Query<(Entity, &Bullet)>,
-    invaders: Query<(Entity, &Invader)>,
-    mut players: Query<(Entity, &Transform, &mut Player)>,
-    mut player_hit_timer: ResMut<PlayerHitTimer>,
-    mut player_hit_animation_timer: ResMut<PlayerHitAnimationTimer>,
-    asset_server: Res<AssetServer>,
-    time: Res<Time>,
+    invaders: Query<Entity, Or<(With<Invader>, With<MysteryShip>)>>,
     windows: Query<&Window>,
-    existing_restart_buttons: Query<Entity, With<GameRestartButton>>,
+    config: Res<GameConfig>,
 ) {
-    if !player_hit_timer.0.finished() {
-        player_hit_timer.0.tick(time.delta());
-
-        let player_hit_1_texture: Handle<Image> = asset_server.load("player-hit-1.png");
-        let player_hit_2_texture: Handle<Image> = asset_server.load("player-hit-2.png");
-
-        player_hit_animation_timer.0.tick(time.delta());
-
-        if player_hit_animation_timer.0.finished() {
-            for (player_entity, player_transform, _) in players.iter_mut() {
-                if time.elapsed().as_secs_f32() % 0.2 < 0.1 {
-                    commands.entity(player_entity).insert(SpriteBundle {
-                        texture: player_hit_2_texture.clone(),
-                        transform: player_transform.clone(),
-                        ..Default::default()
-                    });
-                } else {
-                    commands.entity(player_entity).insert(SpriteBundle {
-                        texture: player_hit_1_texture.clone(),
-                        transform: player_transform.clone(),
-                        ..Default::default()
-                    });
-                }
-            }
-            player_hit_animation_timer.0.reset();
-        }
-    }
-
-    for (bullet_entity, _) in bullets.iter() {
-        commands.entity(bullet_entity).despawn();
-    }
-
-    for (invader_entity, _) in invaders.iter() {
+    for invader_entity in invaders.iter() {
         commands.entity(invader_entity).despawn();
     }
 
-    let mut window_width = WINDOW_WIDTH;
-    let mut window_height = WINDOW_HEIGHT;
-
-    if !existing_restart_buttons.is_empty() {
-        return;
-    }
+    let mut window_width = config.window.width;
+    let mut window_height = config.window.height;
 
     for window in windows.iter() {
         window_width = window.width();
@@ -1011,60 +1832,26 @@ fn game_over(
         });
 }
 
+#[allow(clippy::type_complexity)]
 fn play_again(
     interactions: Query<
         &Interaction,
         (Changed<Interaction>, With<Button>, With<GameRestartButton>),
     >,
     mut windows: Query<&mut Window>,
-    mut game_state: ResMut<GameState>,
-    mut commands: Commands,
-    mut players: Query<(Entity, &Transform, &mut Player)>,
-    game_over_texts: Query<Entity, With<GameOverText>>,
-    game_restart_buttons: Query<Entity, With<GameRestartButton>>,
-    game_restart_texts: Query<Entity, With<GameRestartText>>,
-    scores: Query<Entity, With<PlayerScore>>,
-    lives: Query<Entity, With<PlayerLife>>,
-    shield_parts: Query<Entity, With<ShieldPart>>,
-    mut player_hit_timer: ResMut<PlayerHitTimer>,
+    actions: Res<ActionInput>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
+    if actions.any_just_pressed(Action::Confirm) {
+        next_state.set(GameState::Starting);
+    }
+
     for mut window in windows.iter_mut() {
         for interaction in interactions.iter() {
             match *interaction {
                 Interaction::Pressed => {
-                    for shield_part_entity in shield_parts.iter() {
-                        if shield_parts.get(shield_part_entity).is_ok() {
-                            commands.entity(shield_part_entity).despawn();
-                        }
-                    }
-
-                    for (player_entity, _, _) in players.iter_mut() {
-                        commands.entity(player_entity).despawn();
-                    }
-
-                    for game_over_text_entity in game_over_texts.iter() {
-                        commands.entity(game_over_text_entity).despawn();
-                    }
-
-                    for game_restart_button_entity in game_restart_buttons.iter() {
-                        commands.entity(game_restart_button_entity).despawn();
-                    }
-
-                    for game_restart_text_entity in game_restart_texts.iter() {
-                        commands.entity(game_restart_text_entity).despawn();
-                    }
-
-                    for score_entity in scores.iter() {
-                        commands.entity(score_entity).despawn();
-                    }
-
-                    for life_entity in lives.iter() {
-                        commands.entity(life_entity).despawn();
-                    }
-
-                    player_hit_timer.0.reset();
-
-                    *game_state = GameState::Restarting;
+                    window.cursor.icon = CursorIcon::Default;
+                    next_state.set(GameState::Starting);
                 }
                 Interaction::Hovered => {
                     window.cursor.icon = CursorIcon::Pointer;
@@ -1077,32 +1864,206 @@ fn play_again(
     }
 }
 
+/// Removes everything left from the last game, whether it ended or was left from the pause menu.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn clear_game(
+    mut commands: Commands,
+    players: Query<Entity, With<Player>>,
+    game_over_texts: Query<Entity, With<GameOverText>>,
+    game_restart_buttons: Query<Entity, With<GameRestartButton>>,
+    game_restart_texts: Query<Entity, With<GameRestartText>>,
+    scores: Query<Entity, Or<(With<PlayerScore>, With<HighScoreText>)>>,
+    lives: Query<Entity, Or<(With<PlayerLife>, With<PlayerLivesText>)>>,
+    shields: Query<Entity, With<Shield>>,
+    enemies_and_bullets: Query<Entity, Or<(With<Invader>, With<MysteryShip>, With<Bullet>)>>,
+    mut current_wave: ResMut<CurrentWave>,
+    mut mystery_ship_timer: ResMut<MysteryShipTimer>,
+    mut snapshots: ResMut<TurnSnapshots>,
+) {
+    current_wave.0 = 0;
+    snapshots.0.clear();
+    mystery_ship_timer.0.reset();
+
+    for shield_entity in shields.iter() {
+        commands.entity(shield_entity).despawn();
+    }
+
+    for player_entity in players.iter() {
+        commands.entity(player_entity).despawn();
+    }
+
+    for game_over_text_entity in game_over_texts.iter() {
+        commands.entity(game_over_text_entity).despawn();
+    }
+
+    for game_restart_button_entity in game_restart_buttons.iter() {
+        commands.entity(game_restart_button_entity).despawn();
+    }
+
+    for game_restart_text_entity in game_restart_texts.iter() {
+        commands.entity(game_restart_text_entity).despawn();
+    }
+
+    for score_entity in scores.iter() {
+        commands.entity(score_entity).despawn();
+    }
+
+    for life_entity in lives.iter() {
+        commands.entity(life_entity).despawn();
+    }
+
+    for entity in enemies_and_bullets.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn start_playing(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Playing);
+}
+
 fn check_if_invaders_defeated(
     invaders: Query<(Entity, &Invader)>,
-    mut game_state: ResMut<GameState>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
     if invaders.iter().count() == 0 {
-        *game_state = GameState::RespawningInvaders;
+        next_state.set(GameState::RespawningInvaders);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn respawn_invaders(
     commands: Commands,
     asset_server: Res<AssetServer>,
-    mut game_state: ResMut<GameState>,
+    config: Res<GameConfig>,
+    waves: Res<Waves>,
+    mut current_wave: ResMut<CurrentWave>,
+    march_tempo: ResMut<MarchTempo>,
+    sprite_sheets: Res<SpriteSheets>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    setup_invaders(commands, asset_server);
-    *game_state = GameState::Pausing;
+    current_wave.0 += 1;
+    setup_invaders(
+        commands,
+        asset_server,
+        config,
+        waves,
+        current_wave.into(),
+        march_tempo,
+        sprite_sheets,
+    );
+    next_state.set(GameState::WaveDelay);
+}
+
+fn reset_respawning_invaders_timer(mut respawn_timer: ResMut<RespawningInvadersTimer>) {
+    respawn_timer.0.reset();
 }
 
-fn pause_game(
-    mut game_state: ResMut<GameState>,
+fn wait_for_wave(
+    mut next_state: ResMut<NextState<GameState>>,
     mut respawn_timer: ResMut<RespawningInvadersTimer>,
     time: Res<Time>,
 ) {
     respawn_timer.0.tick(time.delta());
 
     if respawn_timer.0.finished() {
-        *game_state = GameState::Playing;
+        next_state.set(GameState::Playing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::core::FrameCount;
+
+    use super::*;
+    use test_support::headless_app;
+
+    fn run(mut app: App) -> App {
+        while app.should_exit().is_none() {
+            app.update();
+        }
+
+        app
+    }
+
+    fn player_x(app: &mut App) -> f32 {
+        let world = app.world_mut();
+
+        world
+            .query_filtered::<&Transform, With<Player>>()
+            .single(world)
+            .translation
+            .x
+    }
+
+    /// Each player's number, score and lives, then how many invaders are left.
+    fn outcome(app: App) -> (Vec<(u32, u32, u32)>, usize) {
+        let mut app = run(app);
+        let world = app.world_mut();
+        let mut players: Vec<(u32, u32, u32)> = world
+            .query::<&Player>()
+            .iter(world)
+            .map(|player| (player.number, player.score, player.lives))
+            .collect();
+        players.sort();
+
+        let invaders = world.query::<&Invader>().iter(world).count();
+
+        (players, invaders)
+    }
+
+    #[test]
+    fn headless_game_exits_once_the_frame_limit_is_reached() {
+        let app = run(headless_app(1, GameMode::Single, 90, |_| vec![]));
+
+        assert!(app.world().resource::<FrameCount>().0 >= 90);
+    }
+
+    #[test]
+    fn injected_input_moves_the_player() {
+        // The first frame only starts the clock, so this holds right for half a second.
+        let mut app = run(headless_app(1, GameMode::Single, 60, |frame| {
+            if (1..=30).contains(&frame) {
+                vec![vec![Action::MoveRight]]
+            } else {
+                vec![]
+            }
+        }));
+
+        assert!((player_x(&mut app) - (-77.75 + 50.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn autopilot_game_is_frame_exact() {
+        let app = headless_app(1, GameMode::Single, 3000, Autopilot);
+
+        assert_eq!(outcome(app), (vec![(1, 1010, 2)], 5));
+    }
+
+    #[test]
+    fn same_seed_plays_the_same_game() {
+        let first = outcome(headless_app(11, GameMode::CoOp, 600, Autopilot));
+        let second = outcome(headless_app(11, GameMode::CoOp, 600, Autopilot));
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn idle_player_scores_nothing() {
+        let app = headless_app(1, GameMode::Single, 120, |_| vec![vec![]]);
+
+        assert_eq!(outcome(app), (vec![(1, 0, 3)], 55));
+    }
+
+    #[test]
+    fn single_shot_kills_the_invader_above() {
+        // Steps out from behind the first shield under the leftmost column, then fires once the
+        // gun has cooled down.
+        let app = headless_app(1, GameMode::Single, 120, |frame| match frame {
+            0..11 => vec![vec![Action::MoveRight]],
+            35 => vec![vec![Action::Fire]],
+            _ => vec![vec![]],
+        });
+
+        assert_eq!(outcome(app), (vec![(1, 10, 3)], 54));
     }
 }
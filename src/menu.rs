@@ -0,0 +1,548 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    config::GameConfig,
+    enums::{Action, GameMode, GameState, InvaderType, PauseState},
+    high_scores::HighScores,
+    input::{read_bindings, ActionInput, ActionSystem},
+    resources::SpriteSheets,
+};
+
+const TITLE_COLOR: Color = Color::srgb(0.0, 0.91, 0.17);
+const SELECTED_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+const PAUSE_OVERLAY_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+
+/// What the title screen's menu offers, top to bottom.
+#[derive(Clone, Copy)]
+enum TitleEntry {
+    StartOnePlayer,
+    StartTwoPlayers,
+    Options,
+    HighScores,
+    Quit,
+}
+
+impl TitleEntry {
+    const ALL: [TitleEntry; 5] = [
+        TitleEntry::StartOnePlayer,
+        TitleEntry::StartTwoPlayers,
+        TitleEntry::Options,
+        TitleEntry::HighScores,
+        TitleEntry::Quit,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            TitleEntry::StartOnePlayer => "Start 1P",
+            TitleEntry::StartTwoPlayers => "Start 2P",
+            TitleEntry::Options => "Options",
+            TitleEntry::HighScores => "High Scores",
+            TitleEntry::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PauseEntry {
+    Resume,
+    Restart,
+    Options,
+    QuitToTitle,
+}
+
+impl PauseEntry {
+    const ALL: [PauseEntry; 4] = [
+        PauseEntry::Resume,
+        PauseEntry::Restart,
+        PauseEntry::Options,
+        PauseEntry::QuitToTitle,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PauseEntry::Resume => "Resume",
+            PauseEntry::Restart => "Restart",
+            PauseEntry::Options => "Options",
+            PauseEntry::QuitToTitle => "Quit to Title",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum OptionsEntry {
+    TwoPlayerMode,
+    Back,
+}
+
+impl OptionsEntry {
+    const ALL: [OptionsEntry; 2] = [OptionsEntry::TwoPlayerMode, OptionsEntry::Back];
+
+    fn label(self, settings: &MenuSettings) -> String {
+        match self {
+            OptionsEntry::TwoPlayerMode => match settings.two_player_mode {
+                GameMode::CoOp => "2P Mode: Co-op".to_string(),
+                _ => "2P Mode: Alternating".to_string(),
+            },
+            OptionsEntry::Back => "Back".to_string(),
+        }
+    }
+}
+
+/// Choices made on the options screen, kept until the game closes.
+#[derive(Resource)]
+pub struct MenuSettings {
+    /// The mode "Start 2P" starts.
+    pub two_player_mode: GameMode,
+}
+
+impl Default for MenuSettings {
+    fn default() -> Self {
+        MenuSettings {
+            two_player_mode: GameMode::Alternating,
+        }
+    }
+}
+
+/// The highlighted entry of the menu on screen.
+#[derive(Resource, Default)]
+struct MenuCursor(usize);
+
+/// The root of a menu screen, despawned with everything under it when the screen is left.
+#[derive(Component)]
+struct MenuScreen {}
+
+#[derive(Component)]
+struct MenuEntry {
+    index: usize,
+}
+
+/// The title screen, the pause menu and the screens reached from them. Menus are driven by
+/// actions, so they work from the keyboard, a gamepad or a replay alike.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuCursor>()
+            .init_resource::<MenuSettings>()
+            .add_systems(
+                OnEnter(GameState::Title),
+                (reset_menu_cursor, spawn_title_screen),
+            )
+            .add_systems(
+                OnEnter(GameState::Options),
+                (reset_menu_cursor, spawn_options_screen),
+            )
+            .add_systems(OnEnter(GameState::HighScores), spawn_high_scores_screen)
+            .add_systems(OnExit(GameState::Title), despawn_menu_screen)
+            .add_systems(OnExit(GameState::Options), despawn_menu_screen)
+            .add_systems(OnExit(GameState::HighScores), despawn_menu_screen)
+            .add_systems(
+                OnEnter(PauseState::Paused),
+                (reset_menu_cursor, spawn_pause_menu),
+            )
+            .add_systems(
+                OnEnter(PauseState::Options),
+                (reset_menu_cursor, spawn_options_screen),
+            )
+            .add_systems(OnExit(PauseState::Paused), despawn_menu_screen)
+            .add_systems(OnExit(PauseState::Options), despawn_menu_screen)
+            .add_systems(
+                PreUpdate,
+                pause_on_focus_loss
+                    .in_set(ActionSystem)
+                    .after(read_bindings),
+            )
+            .add_systems(
+                Update,
+                (
+                    (move_menu_cursor, choose_title_entry)
+                        .chain()
+                        .run_if(in_state(GameState::Title)),
+                    (move_menu_cursor, choose_option)
+                        .chain()
+                        .run_if(in_state(GameState::Options)),
+                    leave_high_scores.run_if(in_state(GameState::HighScores)),
+                    pause.run_if(in_game).run_if(in_state(PauseState::Running)),
+                    (move_menu_cursor, choose_pause_entry)
+                        .chain()
+                        .run_if(in_state(PauseState::Paused)),
+                    (move_menu_cursor, choose_option)
+                        .chain()
+                        .run_if(in_state(PauseState::Options)),
+                ),
+            )
+            .add_systems(PostUpdate, highlight_menu_entry);
+    }
+}
+
+/// Whether any player picked the highlighted entry this frame.
+fn chosen(actions: &ActionInput) -> bool {
+    actions.any_just_pressed(Action::Confirm) || actions.any_just_pressed(Action::Fire)
+}
+
+fn in_game(state: Res<State<GameState>>) -> bool {
+    state.get().is_in_game()
+}
+
+fn screen_root() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            row_gap: Val::Px(6.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn label(value: impl Into<String>, font_size: f32, color: Color) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font: Default::default(),
+            font_size,
+            color,
+        },
+    )
+}
+
+fn spawn_entries(parent: &mut ChildBuilder, labels: impl IntoIterator<Item = String>) {
+    for (index, value) in labels.into_iter().enumerate() {
+        parent.spawn((label(value, 24.0, Color::WHITE), MenuEntry { index }));
+    }
+}
+
+fn spawn_title_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    sprite_sheets: Res<SpriteSheets>,
+    config: Res<GameConfig>,
+) {
+    let mut score_table: Vec<(ImageBundle, Option<TextureAtlas>, String)> = vec![(
+        ImageBundle {
+            image: UiImage::new(asset_server.load("invader-special.png")),
+            ..Default::default()
+        },
+        None,
+        "= ? MYSTERY".to_string(),
+    )];
+
+    for invader_type in [InvaderType::Squid, InvaderType::Crab, InvaderType::Octopus] {
+        score_table.push((
+            ImageBundle {
+                image: UiImage::new(asset_server.load(invader_type.texture())),
+                ..Default::default()
+            },
+            Some(TextureAtlas {
+                layout: sprite_sheets.invader.clone(),
+                index: 0,
+            }),
+            format!("= {} POINTS", config.scoring.invader_points(invader_type)),
+        ));
+    }
+
+    commands
+        .spawn((screen_root(), MenuScreen {}))
+        .with_children(|parent| {
+            parent.spawn(label("SPACE INVADERS", 50.0, TITLE_COLOR));
+            parent.spawn(label("*SCORE ADVANCE TABLE*", 24.0, Color::WHITE));
+
+            for (mut image, atlas, points) in score_table {
+                image.style = Style {
+                    width: Val::Px(32.0),
+                    height: Val::Px(32.0),
+                    ..Default::default()
+                };
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(10.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .with_children(|row| {
+                        let mut icon = row.spawn(image);
+
+                        if let Some(atlas) = atlas {
+                            icon.insert(atlas);
+                        }
+
+                        row.spawn(label(points, 24.0, Color::WHITE));
+                    });
+            }
+
+            parent.spawn(NodeBundle {
+                style: Style {
+                    height: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+
+            spawn_entries(
+                parent,
+                TitleEntry::ALL.map(|entry| entry.label().to_string()),
+            );
+        });
+}
+
+fn spawn_options_screen(mut commands: Commands, settings: Res<MenuSettings>) {
+    commands
+        .spawn((screen_root(), MenuScreen {}))
+        .with_children(|parent| {
+            parent.spawn(label("OPTIONS", 50.0, TITLE_COLOR));
+            spawn_entries(
+                parent,
+                OptionsEntry::ALL.map(|entry| entry.label(&settings)),
+            );
+        });
+}
+
+/// One column per game mode, each listing its table best first.
+fn spawn_high_scores_screen(mut commands: Commands, high_scores: Res<HighScores>) {
+    commands
+        .spawn((screen_root(), MenuScreen {}))
+        .with_children(|parent| {
+            parent.spawn(label("HIGH SCORES", 50.0, TITLE_COLOR));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(30.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|columns| {
+                    for mode in GameMode::ALL {
+                        let table = high_scores.table(mode);
+                        let mut lines = vec![mode_title(mode).to_string()];
+
+                        lines.extend(table.iter().enumerate().map(|(rank, entry)| {
+                            format!("{:>2}. {} {:>6}", rank + 1, entry.initials, entry.score)
+                        }));
+
+                        if table.is_empty() {
+                            lines.push("---".to_string());
+                        }
+
+                        columns.spawn(label(lines.join("\n"), 20.0, Color::WHITE));
+                    }
+                });
+        });
+}
+
+fn mode_title(mode: GameMode) -> &'static str {
+    match mode {
+        GameMode::Single => "1 PLAYER",
+        GameMode::CoOp => "2P CO-OP",
+        GameMode::Alternating => "2P ALTERNATING",
+    }
+}
+
+fn spawn_pause_menu(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                background_color: PAUSE_OVERLAY_COLOR.into(),
+                ..screen_root()
+            },
+            MenuScreen {},
+        ))
+        .with_children(|parent| {
+            parent.spawn(label("PAUSED", 50.0, TITLE_COLOR));
+            spawn_entries(
+                parent,
+                PauseEntry::ALL.map(|entry| entry.label().to_string()),
+            );
+        });
+}
+
+fn despawn_menu_screen(mut commands: Commands, screens: Query<Entity, With<MenuScreen>>) {
+    for screen in screens.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+}
+
+fn reset_menu_cursor(mut cursor: ResMut<MenuCursor>) {
+    cursor.0 = 0;
+}
+
+/// Up and left move to the previous entry, down and right to the next, wrapping around.
+fn move_menu_cursor(
+    actions: Res<ActionInput>,
+    entries: Query<&MenuEntry>,
+    mut cursor: ResMut<MenuCursor>,
+) {
+    let count = entries.iter().count();
+
+    if count == 0 {
+        return;
+    }
+
+    if actions.any_just_pressed(Action::Up) || actions.any_just_pressed(Action::MoveLeft) {
+        cursor.0 = (cursor.0 + count - 1) % count;
+    }
+
+    if actions.any_just_pressed(Action::Down) || actions.any_just_pressed(Action::MoveRight) {
+        cursor.0 = (cursor.0 + 1) % count;
+    }
+}
+
+fn highlight_menu_entry(cursor: Res<MenuCursor>, mut entries: Query<(&MenuEntry, &mut Text)>) {
+    for (entry, mut text) in entries.iter_mut() {
+        let color = if entry.index == cursor.0 {
+            SELECTED_COLOR
+        } else {
+            Color::WHITE
+        };
+
+        for section in text.sections.iter_mut() {
+            section.style.color = color;
+        }
+    }
+}
+
+fn choose_title_entry(
+    cursor: Res<MenuCursor>,
+    actions: Res<ActionInput>,
+    settings: Res<MenuSettings>,
+    mut mode: ResMut<GameMode>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !chosen(&actions) {
+        return;
+    }
+
+    match TitleEntry::ALL[cursor.0] {
+        TitleEntry::StartOnePlayer => {
+            *mode = GameMode::Single;
+            next_state.set(GameState::Starting);
+        }
+        TitleEntry::StartTwoPlayers => {
+            *mode = settings.two_player_mode;
+            next_state.set(GameState::Starting);
+        }
+        TitleEntry::Options => next_state.set(GameState::Options),
+        TitleEntry::HighScores => next_state.set(GameState::HighScores),
+        TitleEntry::Quit => {
+            exit.send(AppExit::Success);
+        }
+    }
+}
+
+/// Shared by the options screens opened from the title screen and from the pause menu, each
+/// going back to where it was opened from.
+fn choose_option(
+    cursor: Res<MenuCursor>,
+    actions: Res<ActionInput>,
+    mut settings: ResMut<MenuSettings>,
+    mut entries: Query<(&MenuEntry, &mut Text)>,
+    pause_state: Res<State<PauseState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    let mut back = || match pause_state.get() {
+        PauseState::Options => next_pause_state.set(PauseState::Paused),
+        _ => next_state.set(GameState::Title),
+    };
+
+    if actions.any_just_pressed(Action::Pause) {
+        back();
+        return;
+    }
+
+    if !chosen(&actions) {
+        return;
+    }
+
+    let entry = OptionsEntry::ALL[cursor.0];
+
+    match entry {
+        OptionsEntry::TwoPlayerMode => {
+            settings.two_player_mode = match settings.two_player_mode {
+                GameMode::CoOp => GameMode::Alternating,
+                _ => GameMode::CoOp,
+            };
+        }
+        OptionsEntry::Back => {
+            back();
+            return;
+        }
+    }
+
+    for (_, mut text) in entries
+        .iter_mut()
+        .filter(|(menu_entry, _)| menu_entry.index == cursor.0)
+    {
+        text.sections[0].value = entry.label(&settings);
+    }
+}
+
+fn leave_high_scores(actions: Res<ActionInput>, mut next_state: ResMut<NextState<GameState>>) {
+    if chosen(&actions) || actions.any_just_pressed(Action::Pause) {
+        next_state.set(GameState::Title);
+    }
+}
+
+fn pause(actions: Res<ActionInput>, mut next_pause_state: ResMut<NextState<PauseState>>) {
+    if actions.any_just_pressed(Action::Pause) {
+        next_pause_state.set(PauseState::Paused);
+    }
+}
+
+/// Presses the first player's Pause when the window loses focus during a game. Going through
+/// the actions lets the pause reach replays and the other player of a network game like any
+/// other press.
+fn pause_on_focus_loss(
+    windows: Query<&Window>,
+    mut was_focused: Local<bool>,
+    state: Res<State<GameState>>,
+    pause_state: Res<State<PauseState>>,
+    mut actions: ResMut<ActionInput>,
+) {
+    let focused = windows.iter().any(|window| window.focused);
+    let lost_focus = *was_focused && !focused;
+    *was_focused = focused;
+
+    if lost_focus && state.get().is_in_game() && *pause_state.get() == PauseState::Running {
+        actions.press(1, Action::Pause);
+    }
+}
+
+fn choose_pause_entry(
+    cursor: Res<MenuCursor>,
+    actions: Res<ActionInput>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    if actions.any_just_pressed(Action::Pause) {
+        next_pause_state.set(PauseState::Running);
+        return;
+    }
+
+    if !chosen(&actions) {
+        return;
+    }
+
+    match PauseEntry::ALL[cursor.0] {
+        PauseEntry::Resume => next_pause_state.set(PauseState::Running),
+        PauseEntry::Restart => {
+            next_pause_state.set(PauseState::Running);
+            next_state.set(GameState::Starting);
+        }
+        PauseEntry::Options => next_pause_state.set(PauseState::Options),
+        PauseEntry::QuitToTitle => {
+            next_pause_state.set(PauseState::Running);
+            next_state.set(GameState::Title);
+        }
+    }
+}
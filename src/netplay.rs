@@ -0,0 +1,878 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, io,
+    net::{SocketAddr, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    app::{AppExit, MainScheduleOrder},
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    input::InputSystem,
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{Bullet, Invader, Player, Shield},
+    config::{ConfigError, GameConfig},
+    enums::Action,
+    headless::InjectedInputSystem,
+    high_scores::HighScores,
+    input::{ActionInput, ActionSystem},
+    resources::GameRng,
+    waves::Waves,
+};
+
+/// Bumped whenever the packets change so peers running different builds refuse to play together.
+pub const NETPLAY_VERSION: u32 = 2;
+
+/// Both peers step the game by exactly this much every frame so they stay in lockstep.
+const FRAME_TIME: f64 = 1.0 / 60.0;
+
+/// Frames between reading a player's input and simulating it, which gives the input time to
+/// reach the other peer before it is needed.
+const INPUT_DELAY: u32 = 3;
+
+const RESEND_INTERVAL: Duration = Duration::from_millis(16);
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a peer that is leaving keeps answering so the other gets every input it needs.
+const LINGER: Duration = Duration::from_secs(1);
+
+const MAX_INPUTS_PER_PACKET: usize = 64;
+
+/// Checksums of this many recent frames ride along with every input packet.
+const CHECKSUM_HISTORY: usize = 8;
+
+/// Frames of checksums kept for comparing with the peer's.
+const CHECKSUM_WINDOW: u32 = 240;
+
+const MAX_PACKET_SIZE: usize = 65_507;
+
+/// What the host hands the joining peer so both simulate the same game.
+#[derive(Serialize, Deserialize)]
+pub struct SharedGame {
+    pub seed: u64,
+    pub config: GameConfig,
+    pub waves: Waves,
+    /// The host's high-score table, so both peers ask the same players for initials.
+    pub high_scores: HighScores,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    Hello {
+        version: u32,
+    },
+    Welcome(Box<SharedGame>),
+    Rejected {
+        reason: String,
+    },
+    /// The sender's inputs from `first_frame` on, how many of the receiver's inputs it holds,
+    /// and its checksums of recent frames.
+    Inputs {
+        first_frame: u32,
+        inputs: Vec<u8>,
+        received: u32,
+        checksums: Vec<(u32, u64)>,
+    },
+    Goodbye,
+}
+
+#[derive(Debug)]
+pub enum NetplayError {
+    Io(io::Error),
+    Serialize(ron::Error),
+    NoAnswer(SocketAddr),
+    Rejected(String),
+    /// The host sent settings or waves that would not load from a file.
+    Invalid(ConfigError),
+}
+
+impl fmt::Display for NetplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetplayError::Io(error) => write!(f, "{error}"),
+            NetplayError::Serialize(error) => write!(f, "could not serialize packet: {error}"),
+            NetplayError::NoAnswer(address) => write!(f, "no game is being hosted at {address}"),
+            NetplayError::Rejected(reason) => write!(f, "the host refused to play: {reason}"),
+            NetplayError::Invalid(error) => write!(f, "the host's game is invalid: {error}"),
+        }
+    }
+}
+
+impl From<io::Error> for NetplayError {
+    fn from(error: io::Error) -> Self {
+        NetplayError::Io(error)
+    }
+}
+
+/// Holds back and drops outgoing packets, to try netplay over a bad connection on one machine.
+/// With no latency and no loss every packet goes straight out.
+pub struct NetShim {
+    latency: Duration,
+    /// Chance of dropping each packet, from 0 to 1.
+    loss: f32,
+    rng: ChaCha8Rng,
+    queue: VecDeque<(Instant, Vec<u8>)>,
+}
+
+impl NetShim {
+    pub fn new(latency: Duration, loss: f32) -> Self {
+        NetShim {
+            latency,
+            loss,
+            // Kept apart from the game's generator so the shim never changes the game.
+            rng: ChaCha8Rng::seed_from_u64(rand::thread_rng().gen()),
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn send(&mut self, socket: &UdpSocket, peer: SocketAddr, bytes: Vec<u8>) {
+        if self.loss > 0.0 && self.rng.gen_range(0.0..1.0) < self.loss {
+            return;
+        }
+
+        self.queue.push_back((Instant::now() + self.latency, bytes));
+        self.flush(socket, peer);
+    }
+
+    /// Sends every held back packet whose delay is up.
+    fn flush(&mut self, socket: &UdpSocket, peer: SocketAddr) {
+        while let Some((due, _)) = self.queue.front() {
+            if *due > Instant::now() {
+                break;
+            }
+
+            if let Some((_, bytes)) = self.queue.pop_front() {
+                if let Err(error) = socket.send_to(&bytes, peer) {
+                    warn!("Could not send to {}: {}", peer, error);
+                }
+            }
+        }
+    }
+}
+
+/// A co-op game played with another instance over UDP in deterministic lockstep. Each frame
+/// the peers swap their players' input, and a frame is only simulated once both inputs for it
+/// are in, so both run the same game from the same seed. Checksums of every frame are swapped
+/// as well, so a peer whose game drifts apart is caught straight away.
+#[derive(Resource)]
+pub struct NetSession {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    /// Which player this peer controls.
+    pub local_player: u32,
+    shim: NetShim,
+    /// Sent again if the joining peer repeats its hello because the first welcome was lost.
+    welcome: Option<Vec<u8>>,
+    /// Next frame to simulate.
+    frame: u32,
+    local_inputs: Vec<u8>,
+    remote_inputs: Vec<Option<u8>>,
+    /// How many of our inputs the peer holds, counting from the first frame.
+    peer_received: u32,
+    checksums: HashMap<u32, u64>,
+    peer_checksums: HashMap<u32, u64>,
+    recent_checksums: VecDeque<(u32, u64)>,
+    desync: Option<u32>,
+    last_sent: Instant,
+    last_heard: Instant,
+    peer_left: bool,
+    /// Set when the session ended before the frame limit, so nothing more is simulated.
+    closed: bool,
+    /// Whether both players' inputs for this update's frame are in, so the frame is simulated.
+    frame_ready: bool,
+    previous_actions: Vec<Vec<Action>>,
+}
+
+impl NetSession {
+    /// Waits on `port` for another player to join, then sends them `game`. The host is always
+    /// the first player.
+    pub fn host(port: u16, shim: NetShim, game: &SharedGame) -> Result<Self, NetplayError> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        let welcome = ron::to_string(&Packet::Welcome(Box::new(SharedGame {
+            seed: game.seed,
+            config: game.config.clone(),
+            waves: game.waves.clone(),
+            high_scores: game.high_scores.clone(),
+        })))
+        .map_err(NetplayError::Serialize)?
+        .into_bytes();
+
+        println!("Waiting for the second player to join on port {port}");
+
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+
+        loop {
+            let (length, peer) = socket.recv_from(&mut buffer)?;
+
+            match parse(&buffer[..length]) {
+                Some(Packet::Hello { version }) if version == NETPLAY_VERSION => {
+                    let mut session = NetSession::new(socket, peer, 1, shim, Some(welcome))?;
+                    session.send_welcome();
+
+                    return Ok(session);
+                }
+                Some(Packet::Hello { version }) => {
+                    let reason = format!(
+                        "netplay version {version} is not supported (expected {NETPLAY_VERSION})"
+                    );
+                    let rejected = ron::to_string(&Packet::Rejected { reason })
+                        .map_err(NetplayError::Serialize)?;
+                    socket.send_to(rejected.as_bytes(), peer)?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Joins the game hosted at `host` as the second player, returning the game to play.
+    pub fn join(host: SocketAddr, shim: NetShim) -> Result<(Self, SharedGame), NetplayError> {
+        let local = match host {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_read_timeout(Some(Duration::from_millis(250)))?;
+
+        let hello = ron::to_string(&Packet::Hello {
+            version: NETPLAY_VERSION,
+        })
+        .map_err(NetplayError::Serialize)?
+        .into_bytes();
+
+        let mut shim = shim;
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        let started = Instant::now();
+
+        while started.elapsed() < JOIN_TIMEOUT {
+            shim.send(&socket, host, hello.clone());
+
+            let length = match socket.recv_from(&mut buffer) {
+                Ok((length, from)) if from == host => length,
+                Ok(_) => continue,
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        io::ErrorKind::WouldBlock
+                            | io::ErrorKind::TimedOut
+                            | io::ErrorKind::ConnectionReset
+                    ) =>
+                {
+                    shim.flush(&socket, host);
+                    continue;
+                }
+                Err(error) => return Err(NetplayError::Io(error)),
+            };
+
+            match parse(&buffer[..length]) {
+                Some(Packet::Welcome(game)) => {
+                    game.config.validate().map_err(NetplayError::Invalid)?;
+                    game.waves.validate().map_err(NetplayError::Invalid)?;
+
+                    let session = NetSession::new(socket, host, 2, shim, None)?;
+
+                    return Ok((session, *game));
+                }
+                Some(Packet::Rejected { reason }) => return Err(NetplayError::Rejected(reason)),
+                _ => {}
+            }
+        }
+
+        Err(NetplayError::NoAnswer(host))
+    }
+
+    fn new(
+        socket: UdpSocket,
+        peer: SocketAddr,
+        local_player: u32,
+        shim: NetShim,
+        welcome: Option<Vec<u8>>,
+    ) -> Result<Self, NetplayError> {
+        socket.set_nonblocking(true)?;
+
+        // Nobody has pressed anything during the first frames the input delay covers.
+        Ok(NetSession {
+            socket,
+            peer,
+            local_player,
+            shim,
+            welcome,
+            frame: 0,
+            local_inputs: vec![0; INPUT_DELAY as usize],
+            remote_inputs: vec![Some(0); INPUT_DELAY as usize],
+            peer_received: 0,
+            checksums: HashMap::new(),
+            peer_checksums: HashMap::new(),
+            recent_checksums: VecDeque::new(),
+            desync: None,
+            last_sent: Instant::now(),
+            last_heard: Instant::now(),
+            peer_left: false,
+            closed: false,
+            frame_ready: false,
+            previous_actions: Vec::new(),
+        })
+    }
+
+    fn send(&mut self, packet: &Packet) {
+        match ron::to_string(packet) {
+            Ok(contents) => {
+                self.shim
+                    .send(&self.socket, self.peer, contents.into_bytes());
+                self.last_sent = Instant::now();
+            }
+            Err(error) => error!("Could not serialize packet: {}", error),
+        }
+    }
+
+    fn send_welcome(&mut self) {
+        if let Some(welcome) = self.welcome.clone() {
+            self.shim.send(&self.socket, self.peer, welcome);
+        }
+    }
+
+    /// Sends every input the peer has not confirmed yet, so a lost packet is made up for by the
+    /// next one.
+    fn send_inputs(&mut self) {
+        let first_frame = self.peer_received;
+        let inputs = self
+            .local_inputs
+            .iter()
+            .skip(first_frame as usize)
+            .take(MAX_INPUTS_PER_PACKET)
+            .copied()
+            .collect();
+
+        self.send(&Packet::Inputs {
+            first_frame,
+            inputs,
+            received: self.received(),
+            checksums: self.recent_checksums.iter().copied().collect(),
+        });
+    }
+
+    /// How many of the peer's inputs we hold without a gap, counting from the first frame.
+    fn received(&self) -> u32 {
+        self.remote_inputs
+            .iter()
+            .take_while(|input| input.is_some())
+            .count() as u32
+    }
+
+    /// Whether the input for the frame `INPUT_DELAY` ahead of the next one has yet to be read.
+    fn needs_local_input(&self) -> bool {
+        self.local_inputs.len() as u32 <= self.frame + INPUT_DELAY
+    }
+
+    fn remote_input(&self, frame: u32) -> Option<u8> {
+        self.remote_inputs.get(frame as usize).copied().flatten()
+    }
+
+    /// Handles every packet waiting on the socket and sends any held back ones that are due.
+    fn receive(&mut self) {
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+
+        loop {
+            let length = match self.socket.recv_from(&mut buffer) {
+                Ok((length, from)) if from == self.peer => length,
+                Ok(_) => continue,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                // A peer that is not listening yet shows up as a reset on some platforms.
+                Err(error) if error.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(error) => {
+                    warn!("Could not receive from {}: {}", self.peer, error);
+                    break;
+                }
+            };
+
+            self.last_heard = Instant::now();
+
+            match parse(&buffer[..length]) {
+                Some(Packet::Hello { .. }) => self.send_welcome(),
+                Some(Packet::Inputs {
+                    first_frame,
+                    inputs,
+                    received,
+                    checksums,
+                }) => {
+                    // The peer cannot get further ahead than its input delay, so anything
+                    // beyond what one packet could carry past that is garbage.
+                    if first_frame > self.frame + INPUT_DELAY + MAX_INPUTS_PER_PACKET as u32
+                        || inputs.len() > MAX_INPUTS_PER_PACKET
+                        || checksums.len() > CHECKSUM_HISTORY
+                    {
+                        continue;
+                    }
+
+                    for (frame, input) in (first_frame..).zip(inputs) {
+                        if self.remote_inputs.len() <= frame as usize {
+                            self.remote_inputs.resize(frame as usize + 1, None);
+                        }
+
+                        self.remote_inputs[frame as usize] = Some(input);
+                    }
+
+                    self.peer_received = self
+                        .peer_received
+                        .max(received.min(self.local_inputs.len() as u32));
+
+                    for (frame, checksum) in checksums {
+                        match self.checksums.get(&frame) {
+                            Some(local) => self.compare(frame, *local, checksum),
+                            None => {
+                                self.peer_checksums.insert(frame, checksum);
+                            }
+                        }
+                    }
+                }
+                Some(Packet::Goodbye) => self.peer_left = true,
+                _ => {}
+            }
+        }
+
+        self.flush();
+    }
+
+    /// Sends every held back packet whose delay is up.
+    fn flush(&mut self) {
+        self.shim.flush(&self.socket, self.peer);
+    }
+
+    fn record_checksum(&mut self, frame: u32, checksum: u64) {
+        if let Some(remote) = self.peer_checksums.remove(&frame) {
+            self.compare(frame, checksum, remote);
+        }
+
+        self.checksums.insert(frame, checksum);
+        self.recent_checksums.push_back((frame, checksum));
+
+        if self.recent_checksums.len() > CHECKSUM_HISTORY {
+            self.recent_checksums.pop_front();
+        }
+
+        let oldest = frame.saturating_sub(CHECKSUM_WINDOW);
+        self.checksums.retain(|frame, _| *frame >= oldest);
+        self.peer_checksums.retain(|frame, _| *frame >= oldest);
+    }
+
+    fn compare(&mut self, frame: u32, local: u64, remote: u64) {
+        if local != remote && self.desync.is_none_or(|first| frame < first) {
+            self.desync = Some(frame);
+        }
+    }
+}
+
+fn parse(bytes: &[u8]) -> Option<Packet> {
+    ron::de::from_bytes(bytes).ok()
+}
+
+/// Plays a [`NetSession`] inserted as a resource: swaps inputs before each frame, checks the
+/// frame's checksum after it and says goodbye to the peer on exit.
+pub struct NetplayPlugin {
+    /// Spaces frames [`FRAME_TIME`] apart on the wall clock, which a windowed game needs to run
+    /// at its proper speed whatever the monitor's refresh rate. Headless games run flat out.
+    pub paced: bool,
+}
+
+impl Plugin for NetplayPlugin {
+    fn build(&self, app: &mut App) {
+        // Everything between reading input and the end of the update makes up a frame of the
+        // game, which only runs once both players' inputs for it are in.
+        let mut order = app.world_mut().resource_mut::<MainScheduleOrder>();
+        let start = order
+            .labels
+            .iter()
+            .position(|label| *label == PreUpdate.intern());
+        let end = order
+            .labels
+            .iter()
+            .position(|label| *label == Last.intern());
+        let frame_schedules = match (start, end) {
+            (Some(start), Some(end)) => order.labels.drain(start + 1..end).collect(),
+            _ => Vec::new(),
+        };
+        order.insert_after(PreUpdate, NetplayFrame);
+
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            FRAME_TIME,
+        )))
+        .insert_resource(FrameSchedules(frame_schedules))
+        .configure_sets(PreUpdate, InjectedInputSystem.run_if(reads_local_input))
+        .add_systems(
+            PreUpdate,
+            exchange_inputs
+                .after(InputSystem)
+                .after(ActionSystem)
+                .after(InjectedInputSystem),
+        )
+        .add_systems(NetplayFrame, run_frame)
+        .add_systems(PostUpdate, check_sync)
+        .add_systems(Last, leave_session.run_if(on_event::<AppExit>()));
+
+        if self.paced {
+            app.insert_resource(FrameClock(Instant::now()));
+        }
+    }
+}
+
+/// When the next frame is due. Every frame steps the game by [`FRAME_TIME`], so updates that
+/// come sooner, as they do on monitors faster than 60 Hz, skip the frame.
+#[derive(Resource)]
+struct FrameClock(Instant);
+
+/// Runs the [`FrameSchedules`] in place of the main schedule.
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+struct NetplayFrame;
+
+/// The schedules that simulate a frame of the game, skipped on updates spent waiting for the
+/// peer's input.
+#[derive(Resource)]
+struct FrameSchedules(Vec<Interned<dyn ScheduleLabel>>);
+
+fn run_frame(world: &mut World) {
+    if !world.resource::<NetSession>().frame_ready {
+        return;
+    }
+
+    world.resource_scope(|world, schedules: Mut<FrameSchedules>| {
+        for &label in &schedules.0 {
+            let _ = world.try_run_schedule(label);
+        }
+    });
+}
+
+/// Holds back injected input on updates spent waiting for the peer, as the input for the frame
+/// has been read already and the source would otherwise skip ahead of the game.
+fn reads_local_input(session: Res<NetSession>) -> bool {
+    session.needs_local_input()
+}
+
+/// Reads this peer's input, sends it off, and checks whether the peer's input for the frame
+/// about to run is in. If it is, the frame sees both players' actions; if not, the frame waits
+/// for a later update, which sends the same input again if the peer has not confirmed it.
+/// Whichever player a peer controls, its input is read through the first player's bindings.
+fn exchange_inputs(
+    mut session: ResMut<NetSession>,
+    mut actions: ResMut<ActionInput>,
+    clock: Option<ResMut<FrameClock>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    session.frame_ready = false;
+
+    if session.closed || clock.as_ref().is_some_and(|clock| clock.0 > Instant::now()) {
+        return;
+    }
+
+    let frame = session.frame;
+
+    if session.needs_local_input() {
+        let local_input = action_bits(actions.player(1));
+        session.local_inputs.push(local_input);
+        session.send_inputs();
+    } else if session.last_sent.elapsed() >= RESEND_INTERVAL {
+        session.send_inputs();
+    }
+
+    session.receive();
+
+    let Some(remote_input) = session.remote_input(frame) else {
+        if session.peer_left {
+            info!("The other player left the game");
+            session.closed = true;
+            exit.send(AppExit::Success);
+        } else if session.last_heard.elapsed() > PEER_TIMEOUT {
+            error!("The other player stopped responding");
+            session.closed = true;
+            exit.send(AppExit::error());
+        }
+
+        return;
+    };
+
+    let frame_input = session.local_inputs[frame as usize];
+
+    let (first, second) = match session.local_player {
+        1 => (frame_input, remote_input),
+        _ => (remote_input, frame_input),
+    };
+
+    let current = vec![bit_actions(first), bit_actions(second)];
+
+    actions.set_pressed(&session.previous_actions, &current);
+    session.previous_actions = current;
+    session.frame += 1;
+    session.frame_ready = true;
+
+    // Frames that fall behind the clock are not made up for with a burst of frames later.
+    if let Some(mut clock) = clock {
+        clock.0 = (clock.0 + Duration::from_secs_f64(FRAME_TIME)).max(Instant::now());
+    }
+}
+
+/// Packs the actions held into one bit per action, in the order of [`Action::ALL`].
+fn action_bits(actions: &ButtonInput<Action>) -> u8 {
+    Action::ALL
+        .into_iter()
+        .enumerate()
+        .filter(|(_, action)| actions.pressed(*action))
+        .fold(0, |bits, (bit, _)| bits | 1 << bit)
+}
+
+fn bit_actions(bits: u8) -> Vec<Action> {
+    Action::ALL
+        .into_iter()
+        .enumerate()
+        .filter(|(bit, _)| bits & 1 << bit != 0)
+        .map(|(_, action)| action)
+        .collect()
+}
+
+fn check_sync(
+    mut session: ResMut<NetSession>,
+    players: Query<(&Transform, &Player)>,
+    invaders: Query<&Transform, With<Invader>>,
+    bullets: Query<&Transform, With<Bullet>>,
+    shields: Query<&Shield>,
+    rng: Res<GameRng>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if session.closed {
+        return;
+    }
+
+    let frame = session.frame - 1;
+    let checksum = state_checksum(&players, &invaders, &bullets, &shields, &rng);
+    session.record_checksum(frame, checksum);
+
+    if let Some(frame) = session.desync {
+        error!("Desync with the other player detected at frame {}", frame);
+        session.closed = true;
+        exit.send(AppExit::error());
+    }
+}
+
+/// A hash of everything the game's outcome depends on. Entities are hashed on their own and
+/// summed, so the result does not depend on the order queries visit them in.
+fn state_checksum(
+    players: &Query<(&Transform, &Player)>,
+    invaders: &Query<&Transform, With<Invader>>,
+    bullets: &Query<&Transform, With<Bullet>>,
+    shields: &Query<&Shield>,
+    rng: &GameRng,
+) -> u64 {
+    let mut checksum = fnv1a(&rng.0.get_word_pos().to_le_bytes());
+
+    for (transform, player) in players.iter() {
+        let mut bytes = translation_bytes(transform);
+        bytes.extend(player.number.to_le_bytes());
+        bytes.extend(player.lives.to_le_bytes());
+        bytes.extend(player.score.to_le_bytes());
+        checksum = checksum.wrapping_add(fnv1a(&bytes));
+    }
+
+    for transform in invaders.iter().chain(bullets.iter()) {
+        checksum = checksum.wrapping_add(fnv1a(&translation_bytes(transform)));
+    }
+
+    for shield in shields.iter() {
+        let pixels: Vec<u8> = shield.pixels.iter().map(|pixel| *pixel as u8).collect();
+        checksum = checksum.wrapping_add(fnv1a(&pixels));
+    }
+
+    checksum
+}
+
+fn translation_bytes(transform: &Transform) -> Vec<u8> {
+    transform
+        .translation
+        .to_array()
+        .iter()
+        .flat_map(|value| value.to_bits().to_le_bytes())
+        .collect()
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Keeps answering for a moment so the peer gets every input it still needs, then tells it this
+/// peer is gone.
+fn leave_session(mut session: ResMut<NetSession>) {
+    let started = Instant::now();
+
+    while !session.peer_left
+        && session.peer_received < session.local_inputs.len() as u32
+        && started.elapsed() < LINGER
+    {
+        if session.last_sent.elapsed() >= RESEND_INTERVAL {
+            session.send_inputs();
+        }
+
+        thread::sleep(Duration::from_millis(1));
+        session.receive();
+    }
+
+    session.send(&Packet::Goodbye);
+
+    while !session.shim.queue.is_empty() && started.elapsed() < LINGER {
+        thread::sleep(Duration::from_millis(1));
+        session.flush();
+    }
+
+    if session.desync.is_none() {
+        info!(
+            "Stayed in sync with the other player for {} frames",
+            session.frame
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{enums::GameMode, headless::Autopilot, test_support::headless_app};
+
+    fn loopback_pair(latency: Duration, loss: f32) -> (NetSession, NetSession) {
+        let host = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let joiner = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (host_address, joiner_address) =
+            (host.local_addr().unwrap(), joiner.local_addr().unwrap());
+
+        (
+            NetSession::new(host, joiner_address, 1, NetShim::new(latency, loss), None).unwrap(),
+            NetSession::new(joiner, host_address, 2, NetShim::new(latency, loss), None).unwrap(),
+        )
+    }
+
+    /// Each player's number, score and lives, and how long the game took between its first and
+    /// last updates, which leave out starting up and saying goodbye.
+    type Outcome = (Vec<(u32, u32, u32)>, Duration);
+
+    /// Plays co-op through `session` on its own thread.
+    fn play(session: NetSession, frames: u32, paced: bool) -> thread::JoinHandle<Outcome> {
+        thread::spawn(move || {
+            let mut app = headless_app(5, GameMode::CoOp, frames, Autopilot);
+            app.insert_resource(session)
+                .add_plugins(NetplayPlugin { paced });
+
+            app.update();
+            let started = Instant::now();
+            let mut elapsed = Duration::ZERO;
+
+            while app.should_exit().is_none() {
+                elapsed = started.elapsed();
+                app.update();
+            }
+
+            let world = app.world_mut();
+            let session = world.resource::<NetSession>();
+            assert_eq!(session.desync, None);
+            assert!(!session.closed);
+            assert_eq!(session.frame, frames + 1);
+
+            let mut players: Vec<(u32, u32, u32)> = world
+                .query::<&Player>()
+                .iter(world)
+                .map(|player| (player.number, player.score, player.lives))
+                .collect();
+            players.sort();
+
+            (players, elapsed)
+        })
+    }
+
+    #[test]
+    fn peers_stay_in_sync_over_a_poor_connection() {
+        let (host, joiner) = loopback_pair(Duration::from_millis(5), 0.1);
+        let host = play(host, 300, false);
+        let joiner = play(joiner, 300, false);
+
+        let ((host, _), (joiner, _)) = (host.join().unwrap(), joiner.join().unwrap());
+        assert_eq!(host.len(), 2);
+        assert_eq!(host, joiner);
+    }
+
+    #[test]
+    fn paced_peers_play_no_faster_than_sixty_frames_a_second() {
+        let (host, joiner) = loopback_pair(Duration::ZERO, 0.0);
+        let host = play(host, 60, true);
+        let joiner = play(joiner, 60, true);
+
+        // Either peer may spend its first updates waiting on the other, so the quicker of the two
+        // shows the pace.
+        let ((host, host_elapsed), (joiner, joiner_elapsed)) =
+            (host.join().unwrap(), joiner.join().unwrap());
+        assert_eq!(host, joiner);
+        assert!(host_elapsed.min(joiner_elapsed) >= Duration::from_secs_f64(50.0 * FRAME_TIME));
+    }
+
+    #[test]
+    fn join_rejects_a_game_without_waves() {
+        let host = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let host_address = host.local_addr().unwrap();
+
+        let config = GameConfig::default();
+        let mut game = SharedGame {
+            seed: 1,
+            waves: Waves::from_config(&config),
+            config,
+            high_scores: HighScores::default(),
+        };
+        game.waves.waves.clear();
+        let welcome = ron::to_string(&Packet::Welcome(Box::new(game))).unwrap();
+
+        let answer = thread::spawn(move || {
+            let mut buffer = vec![0; MAX_PACKET_SIZE];
+            let (_, joiner) = host.recv_from(&mut buffer).unwrap();
+            host.send_to(welcome.as_bytes(), joiner).unwrap();
+        });
+
+        let joined = NetSession::join(host_address, NetShim::new(Duration::ZERO, 0.0));
+        answer.join().unwrap();
+
+        assert!(matches!(joined, Err(NetplayError::Invalid(_))));
+    }
+
+    #[test]
+    fn receive_ignores_inputs_from_too_far_ahead() {
+        let (mut session, mut peer) = loopback_pair(Duration::ZERO, 0.0);
+        let forged = [
+            (4_000_000_000, vec![1]),
+            (u32::MAX, vec![1, 2]),
+            (INPUT_DELAY, vec![1; MAX_INPUTS_PER_PACKET + 1]),
+        ];
+
+        for (first_frame, inputs) in forged {
+            peer.send(&Packet::Inputs {
+                first_frame,
+                inputs,
+                received: u32::MAX,
+                checksums: Vec::new(),
+            });
+        }
+
+        peer.send(&Packet::Inputs {
+            first_frame: INPUT_DELAY,
+            inputs: vec![1, 2],
+            received: u32::MAX,
+            checksums: Vec::new(),
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        session.receive();
+
+        assert_eq!(session.remote_inputs.len(), INPUT_DELAY as usize + 2);
+        assert_eq!(session.remote_input(INPUT_DELAY + 1), Some(2));
+        assert_eq!(session.peer_received, session.local_inputs.len() as u32);
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{app::AppExit, input::InputSystem, prelude::*, time::TimeUpdateStrategy};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{ConfigError, GameConfig},
+    enums::{Action, GameMode, GameState},
+    high_scores::HighScores,
+    input::{ActionInput, ActionSystem},
+    resources::RngSeed,
+    waves::Waves,
+};
+
+/// Bumped whenever the layout of [`Replay`] changes so old files are rejected instead of
+/// silently playing back differently.
+pub const REPLAY_VERSION: u32 = 16;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub game_version: String,
+    pub seed: u64,
+    /// `None` when the game began on the title screen, where the recorded actions pick the
+    /// mode.
+    pub mode: Option<GameMode>,
+    pub config: GameConfig,
+    pub waves: Waves,
+    /// The high-score table the game began with, which decides who is asked for initials.
+    pub high_scores: HighScores,
+    pub frames: Vec<ReplayFrame>,
+}
+
+/// Everything the game consumed on one frame: how much time passed and which actions each
+/// player held, first player first.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub delta: Duration,
+    pub pressed: Vec<Vec<Action>>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    UnsupportedVersion(u32),
+    /// The replay's game settings or waves would not be accepted from their own files.
+    Invalid(ConfigError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "{error}"),
+            ReplayError::Parse(error) => write!(f, "invalid replay file: {error}"),
+            ReplayError::Serialize(error) => write!(f, "could not serialize replay: {error}"),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay version {version} is not supported (expected {REPLAY_VERSION})"
+            ),
+            ReplayError::Invalid(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        mode: Option<GameMode>,
+        config: GameConfig,
+        waves: Waves,
+        high_scores: HighScores,
+    ) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            game_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed,
+            mode,
+            config,
+            waves,
+            high_scores,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let contents = fs::read_to_string(path).map_err(ReplayError::Io)?;
+        let replay: Replay = ron::from_str(&contents).map_err(ReplayError::Parse)?;
+
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(replay.version));
+        }
+
+        replay.config.validate().map_err(ReplayError::Invalid)?;
+        replay.waves.validate().map_err(ReplayError::Invalid)?;
+
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        let contents =
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new().depth_limit(2))
+                .map_err(ReplayError::Serialize)?;
+
+        fs::write(path, contents).map_err(ReplayError::Io)
+    }
+}
+
+#[derive(Clone)]
+pub enum ReplayMode {
+    Record(PathBuf),
+    Play(Box<Replay>),
+}
+
+/// Records the actions and frame times the game consumes to a replay file, or feeds a recorded
+/// replay back into the same action and time resources the live game uses.
+pub struct ReplayPlugin {
+    pub mode: ReplayMode,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match &self.mode {
+            ReplayMode::Record(path) => {
+                app.insert_resource(ReplayRecorder {
+                    path: path.clone(),
+                    replay: None,
+                })
+                .add_systems(Startup, start_recording)
+                .add_systems(PostUpdate, record_frame)
+                .add_systems(Last, save_recording.run_if(on_event::<AppExit>()));
+            }
+            ReplayMode::Play(replay) => {
+                let first_delta = replay
+                    .frames
+                    .first()
+                    .map(|frame| frame.delta)
+                    .unwrap_or_default();
+
+                app.insert_resource(TimeUpdateStrategy::ManualDuration(first_delta))
+                    .insert_resource(ReplayPlayback {
+                        replay: replay.as_ref().clone(),
+                        frame: 0,
+                    })
+                    .add_systems(PreUpdate, play_frame.after(InputSystem).after(ActionSystem));
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ReplayRecorder {
+    path: PathBuf,
+    replay: Option<Replay>,
+}
+
+#[derive(Resource)]
+struct ReplayPlayback {
+    replay: Replay,
+    frame: usize,
+}
+
+fn start_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    seed: Res<RngSeed>,
+    mode: Res<GameMode>,
+    state: Res<State<GameState>>,
+    config: Res<GameConfig>,
+    waves: Res<Waves>,
+    high_scores: Res<HighScores>,
+) {
+    let mode = (*state.get() != GameState::Title).then_some(*mode);
+    recorder.replay = Some(Replay::new(
+        seed.0,
+        mode,
+        config.clone(),
+        waves.clone(),
+        high_scores.clone(),
+    ));
+}
+
+fn record_frame(mut recorder: ResMut<ReplayRecorder>, actions: Res<ActionInput>, time: Res<Time>) {
+    if let Some(replay) = recorder.replay.as_mut() {
+        replay.frames.push(ReplayFrame {
+            delta: time.delta(),
+            pressed: actions.pressed(),
+        });
+    }
+}
+
+fn save_recording(recorder: Res<ReplayRecorder>) {
+    if let Some(replay) = recorder.replay.as_ref() {
+        match replay.save(&recorder.path) {
+            Ok(()) => info!(
+                "Saved {} frame replay to {}",
+                replay.frames.len(),
+                recorder.path.display()
+            ),
+            Err(error) => error!(
+                "Could not save replay to {}: {}",
+                recorder.path.display(),
+                error
+            ),
+        }
+    }
+}
+
+/// Rebuilds the players' actions on the current replay frame, including which were just
+/// pressed or released, and schedules the next frame's time step.
+fn play_frame(
+    mut playback: ResMut<ReplayPlayback>,
+    mut actions: ResMut<ActionInput>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let frame = playback.frame;
+    let frames = &playback.replay.frames;
+
+    let Some(current) = frames.get(frame) else {
+        info!("Replay finished after {} frames", frames.len());
+        exit.send(AppExit::Success);
+        return;
+    };
+
+    let previous: &[Vec<Action>] = match frame {
+        0 => &[],
+        _ => &frames[frame - 1].pressed,
+    };
+
+    actions.set_pressed(previous, &current.pressed);
+
+    if let Some(next) = frames.get(frame + 1) {
+        *time_update_strategy = TimeUpdateStrategy::ManualDuration(next.delta);
+    }
+
+    playback.frame += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempFile;
+
+    fn replay() -> Replay {
+        let config = GameConfig::default();
+        let waves = Waves::from_config(&config);
+        let mut replay = Replay::new(
+            42,
+            Some(GameMode::CoOp),
+            config,
+            waves,
+            HighScores::default(),
+        );
+
+        replay.frames = vec![
+            ReplayFrame {
+                delta: Duration::from_micros(16_667),
+                pressed: vec![vec![Action::MoveLeft, Action::Fire], vec![]],
+            },
+            ReplayFrame {
+                delta: Duration::from_micros(16_666),
+                pressed: vec![vec![], vec![Action::MoveRight]],
+            },
+        ];
+
+        replay
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let file = TempFile::new("replay-round-trip.ron");
+        replay().save(file.path()).unwrap();
+        let loaded = Replay::load(file.path()).unwrap();
+
+        assert_eq!(loaded.version, REPLAY_VERSION);
+        assert_eq!(loaded.seed, 42);
+        assert_eq!(loaded.mode, Some(GameMode::CoOp));
+        assert_eq!(
+            loaded.waves.get(0).unwrap().formation,
+            replay().waves.get(0).unwrap().formation
+        );
+        assert_eq!(loaded.frames.len(), 2);
+        assert_eq!(loaded.frames[0].delta, Duration::from_micros(16_667));
+        assert_eq!(
+            loaded.frames[0].pressed,
+            [vec![Action::MoveLeft, Action::Fire], vec![]]
+        );
+        assert_eq!(loaded.frames[1].pressed, [vec![], vec![Action::MoveRight]]);
+    }
+
+    #[test]
+    fn load_rejects_other_versions() {
+        let file = TempFile::new("replay-old-version.ron");
+        let mut old = replay();
+        old.version = REPLAY_VERSION + 1;
+        old.save(file.path()).unwrap();
+
+        assert!(matches!(
+            Replay::load(file.path()),
+            Err(ReplayError::UnsupportedVersion(version)) if version == REPLAY_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn load_rejects_an_invalid_config() {
+        let file = TempFile::new("replay-invalid-config.ron");
+        let mut invalid = replay();
+        invalid.config.scoring.mystery_ship.clear();
+        invalid.save(file.path()).unwrap();
+
+        assert!(matches!(
+            Replay::load(file.path()),
+            Err(ReplayError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn load_rejects_a_replay_without_waves() {
+        let file = TempFile::new("replay-no-waves.ron");
+        let mut invalid = replay();
+        invalid.waves.waves.clear();
+        invalid.save(file.path()).unwrap();
+
+        assert!(matches!(
+            Replay::load(file.path()),
+            Err(ReplayError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn load_rejects_garbage() {
+        let file = TempFile::with_contents("replay-garbage.ron", "not a replay");
+
+        assert!(matches!(
+            Replay::load(file.path()),
+            Err(ReplayError::Parse(_))
+        ));
+    }
+}
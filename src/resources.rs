@@ -1,46 +1,111 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    components::{Invader, InvaderBulletProbability, Shield},
+    enums::InvaderDirection,
+};
 
 #[derive(Resource)]
 pub struct InvaderSpeed(pub f32);
 
 #[derive(Resource)]
-pub struct InvaderTimer(pub Timer);
+pub struct InvaderShootProbability(pub f32);
+
+#[derive(Resource)]
+pub struct InvaderBulletProbabilities(pub Vec<InvaderBulletProbability>);
 
 #[derive(Resource)]
-pub struct PlayerShootTimer(pub Timer);
+pub struct InvaderTimer(pub Timer);
+
+/// How fast the formation is currently marching. Updated every frame from the invaders left
+/// so anything that follows the march, like the heartbeat or difficulty, can read it.
+#[derive(Resource, Default)]
+pub struct MarchTempo {
+    /// Seconds between steps.
+    pub interval: f32,
+    pub remaining: usize,
+    pub total: usize,
+}
 
 #[derive(Resource)]
 pub struct InvaderShootTimer(pub Timer);
 
 #[derive(Resource)]
-pub enum GameState {
-    Playing,
-    PlayerHit,
-    RespawningInvaders,
-    Pausing,
-    GameOver,
-    Restarting,
+pub struct PlayerHitTimer(pub Timer);
+
+#[derive(Resource)]
+pub struct PlayerHitAnimationTimer(pub Timer);
+
+#[derive(Resource)]
+pub struct RespawningInvadersTimer(pub Timer);
+
+#[derive(Resource)]
+pub struct MysteryShipTimer(pub Timer);
+
+/// Frame layouts shared by every animated sprite of the same kind.
+#[derive(Resource)]
+pub struct SpriteSheets {
+    pub invader: Handle<TextureAtlasLayout>,
+    pub explosion: Handle<TextureAtlasLayout>,
 }
 
-impl PartialEq for GameState {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (GameState::Playing, GameState::Playing) => true,
-            (GameState::PlayerHit, GameState::PlayerHit) => true,
-            (GameState::RespawningInvaders, GameState::RespawningInvaders) => true,
-            (GameState::Pausing, GameState::Pausing) => true,
-            (GameState::GameOver, GameState::GameOver) => true,
-            (GameState::Restarting, GameState::Restarting) => true,
-            _ => false,
+impl FromWorld for SpriteSheets {
+    fn from_world(world: &mut World) -> Self {
+        let mut layouts = world.resource_mut::<Assets<TextureAtlasLayout>>();
+
+        SpriteSheets {
+            invader: layouts.add(TextureAtlasLayout::from_grid(
+                UVec2::splat(32),
+                2,
+                1,
+                None,
+                None,
+            )),
+            explosion: layouts.add(TextureAtlasLayout::from_grid(
+                UVec2::splat(32),
+                1,
+                1,
+                None,
+                None,
+            )),
         }
     }
 }
 
+/// The seed [`GameRng`] was created from, kept so a game can be reproduced later.
 #[derive(Resource)]
-pub struct PlayerHitTimer(pub Timer);
+pub struct RngSeed(pub u64);
 
+/// The single source of randomness for gameplay. Every random decision draws from this so that
+/// a seed together with the player's input fully determines a game.
 #[derive(Resource)]
-pub struct PlayerHitAnimationTimer(pub Timer);
+pub struct GameRng(pub ChaCha8Rng);
 
-#[derive(Resource)]
-pub struct RespawningInvadersTimer(pub Timer);
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        GameRng(ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+/// Everything a player leaves on screen when their turn ends in an alternating game, keyed by
+/// player number. A player without a snapshot has not had a turn yet.
+#[derive(Resource, Default)]
+pub struct TurnSnapshots(pub HashMap<u32, PlayerSnapshot>);
+
+pub struct PlayerSnapshot {
+    pub wave: usize,
+    pub invaders: Vec<InvaderSnapshot>,
+    pub direction: InvaderDirection,
+    pub shields: Vec<Shield>,
+}
+
+pub struct InvaderSnapshot {
+    pub invader: Invader,
+    pub translation: Vec3,
+    /// Walk frame the invader was showing.
+    pub frame: usize,
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use bevy::math::{IVec2, Rect};
+
+/// A uniform grid that buckets boxes by the cells they cover, so looking up what lies near a
+/// box only touches the handful of items sharing its cells instead of every item.
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    items: Vec<T>,
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+impl<T: Copy> SpatialHash<T> {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialHash {
+            cell_size,
+            items: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Empties the grid. Cells used since the last clear keep their allocations for the next
+    /// rebuild; cells left empty are dropped so the map does not keep every cell ever touched.
+    pub fn clear(&mut self) {
+        self.items.clear();
+
+        self.cells.retain(|_, cell| {
+            let used = !cell.is_empty();
+            cell.clear();
+            used
+        });
+    }
+
+    pub fn insert(&mut self, item: T, aabb: Rect) {
+        let index = self.items.len();
+        self.items.push(item);
+
+        let (min, max) = self.cell_range(aabb);
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    /// Every item sharing a cell with `aabb`, each once and in insertion order. Items are only
+    /// candidates; their boxes still need testing against `aabb`.
+    pub fn query(&self, aabb: Rect) -> Vec<T> {
+        let (min, max) = self.cell_range(aabb);
+        let mut indices = Vec::new();
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if let Some(cell) = self.cells.get(&IVec2::new(x, y)) {
+                    indices.extend_from_slice(cell);
+                }
+            }
+        }
+
+        indices.sort_unstable();
+        indices.dedup();
+
+        indices.into_iter().map(|index| self.items[index]).collect()
+    }
+
+    fn cell_range(&self, aabb: Rect) -> (IVec2, IVec2) {
+        let cell = |value: f32| (value / self.cell_size).floor() as i32;
+
+        (
+            IVec2::new(cell(aabb.min.x), cell(aabb.min.y)),
+            IVec2::new(cell(aabb.max.x), cell(aabb.max.y)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> SpatialHash<u32> {
+        let mut grid = SpatialHash::new(10.0);
+        grid.insert(1, Rect::new(0.0, 0.0, 5.0, 5.0));
+        grid.insert(2, Rect::new(25.0, 25.0, 28.0, 28.0));
+        grid.insert(3, Rect::new(-15.0, -5.0, 15.0, 5.0));
+        grid
+    }
+
+    #[test]
+    fn query_finds_items_sharing_a_cell() {
+        let grid = grid();
+
+        assert_eq!(grid.query(Rect::new(1.0, 1.0, 2.0, 2.0)), [1, 3]);
+        assert_eq!(grid.query(Rect::new(21.0, 21.0, 22.0, 22.0)), [2]);
+        assert_eq!(grid.query(Rect::new(-12.0, -2.0, -11.0, -1.0)), [3]);
+        assert!(grid.query(Rect::new(50.0, 50.0, 60.0, 60.0)).is_empty());
+    }
+
+    #[test]
+    fn query_returns_each_item_once_in_insertion_order() {
+        let grid = grid();
+
+        assert_eq!(grid.query(Rect::new(-20.0, -20.0, 30.0, 30.0)), [1, 2, 3]);
+    }
+
+    #[test]
+    fn clear_empties_the_grid_and_drops_unused_cells() {
+        let mut grid = grid();
+        let everywhere = Rect::new(-20.0, -20.0, 30.0, 30.0);
+
+        grid.clear();
+        assert!(grid.query(everywhere).is_empty());
+
+        grid.insert(4, Rect::new(0.0, 0.0, 1.0, 1.0));
+        grid.clear();
+        assert_eq!(grid.cells.len(), 1);
+
+        grid.clear();
+        assert!(grid.cells.is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    config::GameConfig,
+    enums::GameMode,
+    headless::{HeadlessPlugin, InjectedInput, InputSource, HEADLESS_TIMESTEP},
+    high_scores::{HighScorePlugin, HighScores},
+    input::{ActionPlugin, Bindings},
+    resources::{GameRng, RngSeed},
+    waves::Waves,
+    GamePlugin,
+};
+
+/// Builds a headless `mode` game seeded with `seed` that runs for `frames` frames with `input`
+/// at the controls.
+pub fn headless_app(seed: u64, mode: GameMode, frames: u32, input: impl InputSource) -> App {
+    let config = GameConfig::default();
+    let waves = Waves::from_config(&config);
+    let mut app = App::new();
+
+    app.add_plugins((
+        HeadlessPlugin {
+            frames,
+            timestep: Duration::from_secs_f64(HEADLESS_TIMESTEP),
+        },
+        ActionPlugin {
+            bindings: Bindings::default(),
+        },
+        HighScorePlugin {
+            scores: HighScores::default(),
+            path: None,
+        },
+    ))
+    .insert_resource(InjectedInput::new(input))
+    .insert_resource(RngSeed(seed))
+    .insert_resource(GameRng::from_seed(seed))
+    .add_plugins(GamePlugin {
+        config,
+        waves,
+        mode: Some(mode),
+    });
+
+    app
+}
+
+/// A file in the temporary directory that is deleted when this is dropped, so a failing
+/// assertion does not leave it behind.
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Names the file after `name` and the test process, without creating it.
+    pub fn new(name: &str) -> Self {
+        TempFile(std::env::temp_dir().join(format!("space-invaders-{}-{name}", std::process::id())))
+    }
+
+    pub fn with_contents(name: &str, contents: &str) -> Self {
+        let file = TempFile::new(name);
+        fs::write(file.path(), contents).unwrap();
+
+        file
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
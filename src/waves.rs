@@ -0,0 +1,321 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use ron::extensions::Extensions;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::InvaderBulletProbability,
+    config::{bullet_probabilities, load_ron, positive, probability, ConfigError, GameConfig},
+    enums::{InvaderFireMode, InvaderType},
+};
+
+/// Loaded when no `--waves` flag is given. Without it a single wave is built from the config.
+pub const DEFAULT_WAVES_PATH: &str = "waves.ron";
+
+const INVADER_SPACING: f32 = 16.0;
+
+/// The waves played in order, starting over from the first once the last one is cleared.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Waves {
+    pub waves: Vec<WaveDefinition>,
+}
+
+/// One invader formation. Each string in `formation` is a row, top row first, and each
+/// character a cell: `S`quid, `C`rab, `O`ctopus, or `.` for an empty cell. Every row is the
+/// same length so a cell's index is its column on screen.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaveDefinition {
+    pub formation: Vec<String>,
+    /// Height of the bottom row when the wave spawns.
+    #[serde(default = "default_start_height")]
+    pub start_height: f32,
+    /// Falls back to `invaders.speed` from the config.
+    #[serde(default)]
+    pub march_speed: Option<f32>,
+    /// Falls back to `invaders.shoot_probability` from the config.
+    #[serde(default)]
+    pub shoot_probability: Option<f32>,
+    /// Falls back to `invaders.fire_mode` from the config.
+    #[serde(default)]
+    pub fire_mode: Option<InvaderFireMode>,
+    /// Falls back to `invaders.bullet_probabilities` from the config.
+    #[serde(default)]
+    pub bullet_probabilities: Option<Vec<InvaderBulletProbability>>,
+}
+
+/// An invader placed by a [`WaveDefinition`], positioned relative to the formation's
+/// bottom-centre.
+pub struct WaveInvader {
+    pub invader_type: InvaderType,
+    pub offset: Vec2,
+    pub column: usize,
+}
+
+/// Index into [`Waves`] of the wave currently on screen.
+#[derive(Resource, Default)]
+pub struct CurrentWave(pub usize);
+
+fn default_start_height() -> f32 {
+    6.0
+}
+
+impl Waves {
+    /// Reads the waves at `path` through [`load_ron`], falling back to a single wave built from
+    /// the config.
+    pub fn load(path: &Path, required: bool, config: &GameConfig) -> Result<Self, ConfigError> {
+        load_ron(
+            path,
+            required,
+            ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME),
+            || Waves::from_config(config),
+            Waves::validate,
+        )
+    }
+
+    /// The classic formation: rows of squids over rows of crabs and octopuses.
+    pub fn from_config(config: &GameConfig) -> Self {
+        let row = |invader_type: InvaderType| {
+            invader_type
+                .symbol()
+                .to_string()
+                .repeat(config.invaders.cols)
+        };
+
+        let mut formation = vec![row(InvaderType::Squid); config.invaders.squid_rows];
+        formation.extend(vec![row(InvaderType::Crab); config.invaders.decoy_rows]);
+        formation.extend(vec![row(InvaderType::Octopus); config.invaders.decoy_rows]);
+
+        Waves {
+            waves: vec![WaveDefinition {
+                formation,
+                start_height: default_start_height(),
+                march_speed: None,
+                shoot_probability: None,
+                fire_mode: None,
+                bullet_probabilities: None,
+            }],
+        }
+    }
+
+    /// The wave at `index`, counting on from the first wave again past the last one. Only
+    /// `None` when there are no waves, which [`Waves::validate`] rejects.
+    pub fn get(&self, index: usize) -> Option<&WaveDefinition> {
+        self.waves.get(index.checked_rem(self.waves.len())?)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.waves.is_empty() {
+            return Err(ConfigError::Invalid("waves must not be empty".to_string()));
+        }
+
+        for (i, wave) in self.waves.iter().enumerate() {
+            wave.validate(i)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl WaveDefinition {
+    pub fn invaders(&self) -> Vec<WaveInvader> {
+        let rows = self.formation.len();
+        let mut invaders = Vec::new();
+
+        for (row, cells) in self.formation.iter().enumerate() {
+            let cols = cells.chars().count();
+            let y = (rows - 1 - row) as f32 * INVADER_SPACING;
+
+            for (col, cell) in cells.chars().enumerate() {
+                if let Some(invader_type) = InvaderType::from_symbol(cell) {
+                    let x = (col as f32 - (cols - 1) as f32 / 2.0) * INVADER_SPACING;
+                    invaders.push(WaveInvader {
+                        invader_type,
+                        offset: Vec2::new(x, y),
+                        column: col,
+                    });
+                }
+            }
+        }
+
+        invaders
+    }
+
+    fn validate(&self, index: usize) -> Result<(), ConfigError> {
+        let field = |name: &str| format!("waves[{index}].{name}");
+
+        if !self.start_height.is_finite() {
+            return Err(ConfigError::Invalid(format!(
+                "{} must be a number",
+                field("start_height")
+            )));
+        }
+
+        if let Some(march_speed) = self.march_speed {
+            positive(&field("march_speed"), march_speed)?;
+        }
+
+        if let Some(shoot_probability) = self.shoot_probability {
+            probability(&field("shoot_probability"), shoot_probability)?;
+        }
+
+        if let Some(probabilities) = &self.bullet_probabilities {
+            bullet_probabilities(&field("bullet_probabilities"), probabilities)?;
+        }
+
+        for (row, cells) in self.formation.iter().enumerate() {
+            if let Some(cell) = cells
+                .chars()
+                .find(|cell| *cell != '.' && InvaderType::from_symbol(*cell).is_none())
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "{} has unknown invader '{cell}' (expected S, C, O or .)",
+                    field(&format!("formation[{row}]"))
+                )));
+            }
+        }
+
+        let cols = self
+            .formation
+            .first()
+            .map_or(0, |cells| cells.chars().count());
+
+        if let Some(row) = self
+            .formation
+            .iter()
+            .position(|cells| cells.chars().count() != cols)
+        {
+            return Err(ConfigError::Invalid(format!(
+                "{} must be as long as the first row ({cols} cells)",
+                field(&format!("formation[{row}]"))
+            )));
+        }
+
+        if self.invaders().is_empty() {
+            return Err(ConfigError::Invalid(format!(
+                "{} has no invaders",
+                field("formation")
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempFile;
+
+    fn wave(formation: &[&str]) -> WaveDefinition {
+        WaveDefinition {
+            formation: formation.iter().map(|row| row.to_string()).collect(),
+            start_height: default_start_height(),
+            march_speed: None,
+            shoot_probability: None,
+            fire_mode: None,
+            bullet_probabilities: None,
+        }
+    }
+
+    #[test]
+    fn from_config_builds_the_classic_formation() {
+        let config = GameConfig::default();
+        let waves = Waves::from_config(&config);
+        let formation = &waves.get(0).unwrap().formation;
+
+        assert_eq!(
+            formation.len(),
+            config.invaders.squid_rows + 2 * config.invaders.decoy_rows
+        );
+        assert_eq!(formation[0], "S".repeat(config.invaders.cols));
+        assert_eq!(
+            formation[formation.len() - 1],
+            "O".repeat(config.invaders.cols)
+        );
+        assert!(waves.validate().is_ok());
+    }
+
+    #[test]
+    fn invaders_are_centred_on_the_bottom_row() {
+        let invaders = wave(&["S.S", "COC"]).invaders();
+
+        assert_eq!(invaders.len(), 5);
+        assert_eq!(
+            invaders[0].offset,
+            Vec2::new(-INVADER_SPACING, INVADER_SPACING)
+        );
+        assert_eq!(
+            invaders[1].offset,
+            Vec2::new(INVADER_SPACING, INVADER_SPACING)
+        );
+        assert_eq!(invaders[1].column, 2);
+        assert_eq!(invaders[3].invader_type, InvaderType::Octopus);
+        assert_eq!(invaders[3].offset, Vec2::ZERO);
+    }
+
+    #[test]
+    fn validate_rejects_bad_formations() {
+        assert!(wave(&["SXS"]).validate(0).is_err());
+        assert!(wave(&["...", "..."]).validate(0).is_err());
+        assert!(wave(&["SSSSS", "CCC"]).validate(0).is_err());
+        assert!(wave(&["SSS", "CCC"]).validate(0).is_ok());
+    }
+
+    #[test]
+    fn get_starts_over_after_the_last_wave() {
+        let waves = Waves {
+            waves: vec![wave(&["S"]), wave(&["C"])],
+        };
+
+        assert_eq!(waves.get(2).unwrap().formation, ["S"]);
+        assert_eq!(waves.get(3).unwrap().formation, ["C"]);
+    }
+
+    #[test]
+    fn get_finds_nothing_without_waves() {
+        let waves = Waves { waves: Vec::new() };
+
+        assert!(waves.get(0).is_none());
+        assert!(waves.validate().is_err());
+    }
+
+    #[test]
+    fn load_parses_optional_fields_without_some() {
+        let file = TempFile::with_contents(
+            "waves-optional.ron",
+            r#"(waves: [(formation: ["SSS"], march_speed: 40.0, fire_mode: Aimed)])"#,
+        );
+        let waves = Waves::load(file.path(), true, &GameConfig::default()).unwrap();
+
+        let wave = waves.get(0).unwrap();
+
+        assert_eq!(wave.march_speed, Some(40.0));
+        assert_eq!(wave.shoot_probability, None);
+        assert!(matches!(wave.fire_mode, Some(InvaderFireMode::Aimed)));
+        assert_eq!(wave.start_height, default_start_height());
+    }
+
+    #[test]
+    fn load_rejects_invalid_waves() {
+        let empty = TempFile::with_contents("waves-empty.ron", "(waves: [])");
+        let unknown_field = TempFile::with_contents(
+            "waves-unknown.ron",
+            r#"(waves: [(formation: ["S"], speed: 1.0)])"#,
+        );
+
+        assert!(Waves::load(empty.path(), true, &GameConfig::default()).is_err());
+        assert!(Waves::load(unknown_field.path(), true, &GameConfig::default()).is_err());
+    }
+
+    #[test]
+    fn missing_file_falls_back_to_the_config_unless_required() {
+        let file = TempFile::new("waves-missing.ron");
+        let config = GameConfig::default();
+
+        assert!(Waves::load(file.path(), false, &config).is_ok());
+        assert!(Waves::load(file.path(), true, &config).is_err());
+    }
+}